@@ -0,0 +1,143 @@
+// #[derive(VertexLayout)]: the vertex buffer layout of a struct from its fields
+//
+// #[repr(C)]
+// #[derive(VertexLayout)]
+// #[step_mode(Instance)]      // Vertex when omitted
+// struct InstanceRaw {
+//     #[location(5)]
+//     model: [[f32; 4]; 4],   // a matrix takes one location per column: 5 to 8
+//     #[location(9)]
+//     uv_rect: [f32; 4],
+// }
+//
+// The formats come from the VertexAttributeType trait of the field types
+// and the offsets from offset_of!, the overlapping locations are checked
+// when compiling (see learn_wgpu::vertex_layout).
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt};
+
+#[proc_macro_derive(VertexLayout, attributes(location, step_mode))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match vertex_layout(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn vertex_layout(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "VertexLayout can't be derived for generic structs",
+        ));
+    }
+    // the field offsets are only meaningful with a defined layout
+    let has_repr_c = input.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|repr| repr == "C")
+    });
+    if !has_repr_c {
+        return Err(syn::Error::new_spanned(
+            name,
+            "VertexLayout needs #[repr(C)], the GPU reads the fields in order",
+        ));
+    }
+
+    let mut step_mode = quote!(Vertex);
+    for attr in &input.attrs {
+        if attr.path().is_ident("step_mode") {
+            let mode = attr.parse_args::<syn::Ident>()?;
+            if mode != "Vertex" && mode != "Instance" {
+                return Err(syn::Error::new_spanned(
+                    mode,
+                    "the step mode is Vertex or Instance",
+                ));
+            }
+            step_mode = quote!(#mode);
+        }
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "VertexLayout needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "VertexLayout can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut types = Vec::new();
+    let mut idents = Vec::new();
+    let mut locations = Vec::new();
+    for field in fields {
+        let location = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("location"))
+            .ok_or_else(|| {
+                syn::Error::new_spanned(
+                    field,
+                    "missing #[location(n)], the @location in the shader",
+                )
+            })?
+            .parse_args::<LitInt>()?
+            .base10_parse::<u32>()?;
+        types.push(&field.ty);
+        idents.push(field.ident.as_ref().unwrap());
+        locations.push(location);
+    }
+
+    Ok(quote! {
+        impl ::learn_wgpu::VertexLayout for #name {
+            const STEP_MODE: ::wgpu::VertexStepMode = ::wgpu::VertexStepMode::#step_mode;
+            const ATTRIBUTES: &'static [::wgpu::VertexAttribute] = &{
+                const COUNT: usize = 0 #(
+                    + <#types as ::learn_wgpu::vertex_layout::VertexAttributeType>::FORMATS.len()
+                )*;
+                let mut attributes = [::wgpu::VertexAttribute {
+                    format: ::wgpu::VertexFormat::Float32,
+                    offset: 0,
+                    shader_location: 0,
+                }; COUNT];
+                let mut i = 0;
+                #(
+                    let formats =
+                        <#types as ::learn_wgpu::vertex_layout::VertexAttributeType>::FORMATS;
+                    let mut offset = ::std::mem::offset_of!(#name, #idents) as ::wgpu::BufferAddress;
+                    let mut j = 0;
+                    while j < formats.len() {
+                        attributes[i] = ::wgpu::VertexAttribute {
+                            format: formats[j],
+                            offset,
+                            shader_location: #locations + j as u32,
+                        };
+                        offset += formats[j].size();
+                        i += 1;
+                        j += 1;
+                    }
+                )*
+                ::learn_wgpu::vertex_layout::assert_distinct_locations(&attributes, &[]);
+                attributes
+            };
+        }
+
+        // evaluated now, so overlapping locations don't compile
+        const _: () = {
+            let _ = <#name as ::learn_wgpu::VertexLayout>::ATTRIBUTES;
+        };
+    })
+}
@@ -0,0 +1,68 @@
+// A scene built outside of the library: a textured square
+// drawn three times, in a window driven by event_loop::run_with
+use learn_wgpu::{
+    event_loop, Camera, Instance, Material, Mesh, Model, MyTexture, Renderer, Scene, Vertex,
+};
+
+const SQUARE_VERTICES: &[Vertex] = &[
+    Vertex::new([-0.5, -0.5, 0.0], [0.0, 1.0], [0.0, 0.0, 1.0]),
+    Vertex::new([0.5, -0.5, 0.0], [1.0, 1.0], [0.0, 0.0, 1.0]),
+    Vertex::new([0.5, 0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+    Vertex::new([-0.5, 0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+];
+
+const SQUARE_INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
+
+fn build_scene(renderer: &Renderer) -> Scene {
+    let device = renderer.device();
+    let config = renderer.config();
+    let camera = Camera::look_at(
+        (0.0, 0.0, 3.0).into(),
+        (0.0, 0.0, 0.0).into(),
+        config.width as f32 / config.height as f32,
+    )
+    .with_fovy(60.0);
+    let mut scene = Scene::new(renderer, camera);
+
+    let texture = MyTexture::new(
+        device,
+        renderer.queue(),
+        include_bytes!("../src/textures/happy-tree.png"),
+    )
+    .unwrap();
+    let material = Material::new(
+        device,
+        renderer.material_bind_group_layout(),
+        "happy-tree",
+        texture,
+    );
+    let square = Model {
+        meshes: vec![Mesh::new(
+            device,
+            "square",
+            SQUARE_VERTICES,
+            SQUARE_INDICES,
+            0,
+        )],
+        materials: vec![material],
+    };
+
+    let instances = (-1..=1)
+        .map(|x| {
+            Instance::new((x as f32 * 1.2, 0.0, 0.0).into()).with_rotation(
+                cgmath::Quaternion::from(cgmath::Euler::new(
+                    cgmath::Deg(0.0),
+                    cgmath::Deg(0.0),
+                    cgmath::Deg(x as f32 * 20.0),
+                )),
+            )
+        })
+        .collect();
+    scene.add_model(device, square, instances);
+
+    scene
+}
+
+fn main() {
+    pollster::block_on(event_loop::run_with(build_scene));
+}
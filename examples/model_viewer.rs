@@ -0,0 +1,60 @@
+// Show a model file
+// OBJ files are drawn on an instance grid:
+// cargo run --example model_viewer -- src/models/cube.obj
+// glTF files are whole scenes, with their own camera when they have one:
+// cargo run --example model_viewer -- src/models/scene.gltf
+// both in front of the sky of src/textures/sky.hdr
+use cgmath::prelude::*;
+use learn_wgpu::{event_loop, resources, Camera, Instance, Renderer, Scene};
+
+fn obj_scene(renderer: &Renderer, path: &str) -> Scene {
+    let config = renderer.config();
+    let camera = Camera::look_at(
+        (0.0, 5.0, 10.0).into(),
+        (0.0, 0.0, 0.0).into(),
+        config.width as f32 / config.height as f32,
+    );
+    let mut scene = Scene::new(renderer, camera);
+
+    let model = resources::load_obj(renderer, path).unwrap();
+    let instances = (0..10)
+        .flat_map(|z| {
+            (0..10).map(move |x| {
+                let position = cgmath::Vector3::new(x as f32 - 5.0, 0.0, z as f32 - 5.0) * 1.5;
+                Instance::new(position).with_rotation(cgmath::Quaternion::from_angle_y(
+                    cgmath::Deg((x * 10 + z) as f32 * 7.0),
+                ))
+            })
+        })
+        .collect();
+    scene.add_model(renderer.device(), model, instances);
+
+    scene
+}
+
+fn build_scene(renderer: &Renderer, path: &str) -> Scene {
+    let mut scene = if path.ends_with(".gltf") || path.ends_with(".glb") {
+        resources::load_gltf(renderer, path).unwrap()
+    } else {
+        obj_scene(renderer, path)
+    };
+
+    let sky = resources::load_equirectangular(
+        renderer,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/sky.hdr"),
+        64,
+    )
+    .unwrap();
+    scene.set_skybox(renderer.device(), Some(sky));
+
+    scene
+}
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "src/models/cube.obj".to_string());
+    pollster::block_on(event_loop::run_with(|renderer| {
+        build_scene(renderer, &path)
+    }));
+}
@@ -1,14 +1,30 @@
 // in normalized device coordinates (opens new window), wgpu is based on Metal and Directx
 // the x-axis and y-axis are in the range of -1.0 to +1.0, and the z-axis is 0.0 to +1.0
 // in OpenGL z-axis is -1.0 to 1.0
+// so z is remapped with z' = 0.5 * z + 0.5 * w
+// careful: Matrix4::new takes the values column by column
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
+// What the shaders know about the camera
+// the eye position is needed for specular lighting
+// vec3 would be padded to 16 bytes in WGSL uniforms, so a vec4 is used
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view_position: [f32; 4],
+    view_proj: [[f32; 4]; 4],
+    // from clip space back to the world, for the skybox directions
+    inv_view_proj: [[f32; 4]; 4],
+    // znear and zfar, for the depth view (the last two are padding)
+    clip_planes: [f32; 4],
+}
+
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
@@ -21,31 +37,86 @@ pub struct Camera {
 
 impl Camera {
     pub fn new(config: &wgpu::SurfaceConfiguration) -> Self {
-        Self {
+        Self::look_at(
             // position the camera 1 unit up and 2 units back
             // +z is out of the screen
-            eye: (0.0, 1.0, 2.0).into(),
+            (0.0, 1.0, 2.0).into(),
             // have it look at the origin
-            target: (0.0, 0.0, 0.0).into(),
+            (0.0, 0.0, 0.0).into(),
+            config.width as f32 / config.height as f32,
+        )
+    }
+
+    // A camera at eye looking at target, with y as "up",
+    // a 45° vertical field of view and clip planes at 0.1 and 100
+    pub fn look_at(eye: cgmath::Point3<f32>, target: cgmath::Point3<f32>, aspect: f32) -> Self {
+        Self {
+            eye,
+            target,
             // which way is "up"
             up: cgmath::Vector3::unit_y(),
-            aspect: config.width as f32 / config.height as f32,
+            aspect,
             fovy: 45.0,
             znear: 0.1,
             zfar: 100.0,
         }
     }
+
+    pub fn with_up(mut self, up: cgmath::Vector3<f32>) -> Self {
+        self.up = up;
+        self
+    }
+
+    // vertical field of view, in degrees
+    pub fn with_fovy(mut self, fovy: f32) -> Self {
+        self.fovy = fovy;
+        self
+    }
+
+    pub fn with_clip_planes(mut self, znear: f32, zfar: f32) -> Self {
+        self.znear = znear;
+        self.zfar = zfar;
+        self
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    // has to follow the size of the surface, or the image gets stretched
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
     fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
 
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        OPENGL_TO_WGPU_MATRIX * proj * view
     }
     // We can't use cgmath with bytemuck directly, so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
-    // TODO: in the original code, CameraUniform was using a repr(C)
-    // I do not see the point here as we use plain array ?
-    pub fn get_uniform(&self) -> [[f32; 4]; 4] {
-        self.build_view_projection_matrix().into()
+    pub fn get_uniform(&self) -> CameraUniform {
+        let view_proj = self.build_view_projection_matrix();
+        CameraUniform {
+            view_position: [self.eye.x, self.eye.y, self.eye.z, 1.0],
+            view_proj: view_proj.into(),
+            inv_view_proj: cgmath::SquareMatrix::invert(&view_proj)
+                .unwrap_or(cgmath::SquareMatrix::identity())
+                .into(),
+            clip_planes: [self.znear, self.zfar, 0.0, 0.0],
+        }
     }
 }
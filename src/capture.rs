@@ -0,0 +1,90 @@
+// Getting pixels back from the GPU
+// a texture can't be read directly, it has to be copied to a buffer
+// that we can map on the CPU side
+
+// copy_texture_to_buffer needs rows aligned on 256 bytes
+// so a 100 pixels wide Rgba8 texture (400 bytes) will use 512 bytes per row
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded_bytes_per_row = 4 * width;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded_bytes_per_row.div_ceil(align) * align
+}
+
+// Read a 2D texture (Rgba8 or Bgra8, the formats we render to) into an RGBA image.
+// The texture must have been created with TextureUsages::COPY_SRC
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> image::RgbaImage {
+    let width = texture.width();
+    let height = texture.height();
+    let padded_bytes_per_row = padded_bytes_per_row(width);
+
+    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        // MAP_READ so we can read it on the CPU side
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Capture Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &output_buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    // mapping is asynchronous, the callback is called once the GPU is done
+    // with the copy, and poll(Wait) blocks until it happens
+    let buffer_slice = output_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).unwrap();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .unwrap()
+        .expect("failed to map the capture buffer");
+
+    let swap_red_blue = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => panic!("capture of {format:?} textures is not supported"),
+    };
+
+    // remove the row padding
+    let mut pixels = Vec::with_capacity((4 * width * height) as usize);
+    {
+        let data = buffer_slice.get_mapped_range();
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..(4 * width) as usize]);
+        }
+    }
+    output_buffer.unmap();
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels).unwrap()
+}
@@ -0,0 +1,176 @@
+use crate::{
+    mytexture::MyTexture,
+    pipeline::{compile_shader, PassPipeline, PendingPipeline, ShaderPass},
+    preprocessor::{create_builtin_module, ShaderPreprocessor},
+    scene::Scene,
+};
+
+// Debug pass drawing the depth buffer over the frame
+// as a fullscreen triangle, see depth_visualization.wgsl
+pub struct DepthPass {
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: PassPipeline,
+}
+
+impl DepthPass {
+    pub fn new(
+        device: &wgpu::Device,
+        depth_texture: &MyTexture,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // read with textureLoad, so no sampler
+                // bound as a float texture as GL can only read
+                // depth textures through comparisons
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("depth_pass_bind_group_layout"),
+        });
+
+        let bind_group = Self::create_bind_group(device, &layout, depth_texture);
+
+        let source = include_str!("shaders/depth_visualization.wgsl");
+        let shader = create_builtin_module(device, "Depth Pass Shader", source);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Pass Pipeline Layout"),
+            // the camera gives the clip planes
+            bind_group_layouts: &[&layout, camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, format, &shader);
+
+        Self {
+            layout,
+            bind_group,
+            format,
+            pipeline_layout,
+            pipeline: PassPipeline::new(pipeline, source),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Pass Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                // the triangle is generated from vertex_index
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // the depth texture is read, it can't be attached at the same time
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth_texture: &MyTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+            }],
+            label: Some("depth_pass_bind_group"),
+        })
+    }
+
+    // the depth texture is recreated on resize, so the bind group has to follow
+    pub fn resize(&mut self, device: &wgpu::Device, depth_texture: &MyTexture) {
+        self.bind_group = Self::create_bind_group(device, &self.layout, depth_texture);
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        scene: &Scene,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Visual Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // every pixel is overwritten, no need to clear
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(self.pipeline.get());
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, scene.camera_bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl ShaderPass for DepthPass {
+    fn shader_file(&self) -> &'static str {
+        "depth_visualization.wgsl"
+    }
+
+    fn pipeline_mut(&mut self) -> &mut PassPipeline {
+        &mut self.pipeline
+    }
+
+    fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        let (shader, _) = compile_shader(device, preprocessor, "Depth Pass Shader", source, &[])?;
+        let pending = PendingPipeline::new(device, || {
+            Self::create_pipeline(device, &self.pipeline_layout, self.format, &shader)
+        });
+        self.pipeline.replace(source, pending);
+        Ok(())
+    }
+}
@@ -3,17 +3,93 @@ use winit::{
     window::WindowBuilder,
 };
 
-use crate::state::State;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use cgmath::Rotation3;
+
+use crate::{renderer::Renderer, scene::Scene, state::State};
+
+// how fast the instances of the demo turn
+const DEMO_ROTATION_SPEED: cgmath::Deg<f32> = cgmath::Deg(90.0);
+
+// What the window does besides drawing the scene,
+// the default is nothing more
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    // development mode: the shaders of this directory are compiled again
+    // when they are edited, see Renderer::watch_shaders
+    pub shader_dir: Option<PathBuf>,
+}
 
+impl RunOptions {
+    pub fn with_shader_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.shader_dir = Some(dir.into());
+        self
+    }
+}
+
+// Open a window showing the demo scene, its pentagons turning on themselves
 pub async fn run() {
+    run_demo(RunOptions::default()).await;
+}
+
+// Same as run, with options
+pub async fn run_demo(options: RunOptions) {
+    run_with_options(
+        Scene::demo,
+        |scene, dt| {
+            let rotation = cgmath::Quaternion::from_angle_y(DEMO_ROTATION_SPEED * dt.as_secs_f32());
+            for instance in scene.instances_mut(0).iter_mut() {
+                instance.rotation = instance.rotation * rotation;
+            }
+        },
+        options,
+    )
+    .await;
+}
+
+// Open a window showing the scene returned by build_scene
+// it is given the renderer to create its meshes, materials and buffers
+pub async fn run_with<F>(build_scene: F)
+where
+    F: FnOnce(&Renderer) -> Scene,
+{
+    run_with_update(build_scene, |_, _| {}).await;
+}
+
+// Same as run_with, update_scene is also called before each frame
+// with the time since the previous one, to animate the scene
+pub async fn run_with_update<F, U>(build_scene: F, update_scene: U)
+where
+    F: FnOnce(&Renderer) -> Scene,
+    U: FnMut(&mut Scene, Duration),
+{
+    run_with_options(build_scene, update_scene, RunOptions::default()).await;
+}
+
+// Same as run_with_update, with options
+pub async fn run_with_options<F, U>(build_scene: F, mut update_scene: U, options: RunOptions)
+where
+    F: FnOnce(&Renderer) -> Scene,
+    U: FnMut(&mut Scene, Duration),
+{
     env_logger::init();
 
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
-    // State::new uses async code, so we're going to wait for it to finish
-    let mut state = State::new(&window).await;
+    // Renderer::new uses async code, so we're going to wait for it to finish
+    let renderer = Renderer::new(&window).await;
+    let scene = build_scene(&renderer);
+    let mut state = State::from_parts(renderer, scene);
+    if let Some(dir) = options.shader_dir {
+        state.renderer_mut().watch_shaders(dir);
+    }
     let mut surface_configured = false;
+    let mut last_frame = Instant::now();
 
     event_loop
         .run(move |event, control_flow| {
@@ -21,55 +97,56 @@ pub async fn run() {
                 Event::WindowEvent {
                     ref event,
                     window_id,
-                } if window_id == state.window().id() => {
-                    if !state.input(event) {
-                        match event {
-                            WindowEvent::CloseRequested
-                            | WindowEvent::KeyboardInput {
-                                event:
-                                    KeyEvent {
-                                        state: ElementState::Pressed,
-                                        physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                        ..
-                                    },
-                                ..
-                            } => control_flow.exit(),
-                            WindowEvent::Resized(physical_size) => {
-                                surface_configured = true;
-                                state.resize(*physical_size);
+                } if window_id == state.window().id() && !state.input(event) => {
+                    match event {
+                        WindowEvent::CloseRequested
+                        | WindowEvent::KeyboardInput {
+                            event:
+                                KeyEvent {
+                                    state: ElementState::Pressed,
+                                    physical_key: PhysicalKey::Code(KeyCode::Escape),
+                                    ..
+                                },
+                            ..
+                        } => control_flow.exit(),
+                        WindowEvent::Resized(physical_size) => {
+                            surface_configured = true;
+                            state.resize(*physical_size);
+                        }
+                        WindowEvent::RedrawRequested => {
+                            // This tells winit that we want another frame after this one
+                            state.window().request_redraw();
+
+                            if !surface_configured {
+                                return;
                             }
-                            WindowEvent::RedrawRequested => {
-                                // This tells winit that we want another frame after this one
-                                state.window().request_redraw();
 
-                                if !surface_configured {
-                                    return;
+                            let now = Instant::now();
+                            update_scene(state.scene_mut(), now - last_frame);
+                            last_frame = now;
+                            state.update();
+
+                            match state.render() {
+                                Ok(_) => {}
+                                // Reconfigure the surface if it's lost or outdated
+                                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                                    state.resize(state.size())
+                                }
+                                // The system is out of memory, we should probably quit
+                                Err(
+                                    wgpu::SurfaceError::OutOfMemory | wgpu::SurfaceError::Other,
+                                ) => {
+                                    log::error!("OutOfMemory");
+                                    control_flow.exit();
                                 }
 
-                                state.update();
-
-                                match state.render() {
-                                    Ok(_) => {}
-                                    // Reconfigure the surface if it's lost or outdated
-                                    Err(
-                                        wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
-                                    ) => state.resize(state.size),
-                                    // The system is out of memory, we should probably quit
-                                    Err(
-                                        wgpu::SurfaceError::OutOfMemory | wgpu::SurfaceError::Other,
-                                    ) => {
-                                        log::error!("OutOfMemory");
-                                        control_flow.exit();
-                                    }
-
-                                    // This happens when the a frame takes too long to present
-                                    Err(wgpu::SurfaceError::Timeout) => {
-                                        log::warn!("Surface timeout")
-                                    }
+                                // This happens when the a frame takes too long to present
+                                Err(wgpu::SurfaceError::Timeout) => {
+                                    log::warn!("Surface timeout")
                                 }
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
                 _ => {}
@@ -0,0 +1,85 @@
+use winit::{
+    event::*,
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{camera::Camera, camera_controller::CameraController};
+
+// Keyboard handling: the camera controller
+// and the keys toggling rendering options
+pub struct Input {
+    camera_controller: CameraController,
+    use_color: bool,
+    show_depth: bool,
+    screenshot_requested: bool,
+}
+
+impl Input {
+    pub fn new(camera_speed: f32) -> Self {
+        Self {
+            camera_controller: CameraController::new(camera_speed),
+            use_color: false,
+            show_depth: false,
+            screenshot_requested: false,
+        }
+    }
+
+    // returns true if the event has been consumed
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        let camera_controlled = self.camera_controller.process_events(event);
+        let key_controlled = match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        physical_key: PhysicalKey::Code(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                let is_released = *state == ElementState::Released;
+                match keycode {
+                    KeyCode::Space => {
+                        if is_released {
+                            self.use_color = !self.use_color
+                        };
+                        true
+                    }
+                    KeyCode::KeyV => {
+                        if is_released {
+                            self.show_depth = !self.show_depth
+                        };
+                        true
+                    }
+                    KeyCode::F12 => {
+                        if is_released {
+                            self.screenshot_requested = true
+                        };
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+
+        camera_controlled || key_controlled
+    }
+
+    pub fn update_camera(&self, camera: &mut Camera) {
+        self.camera_controller.update_camera(camera);
+    }
+
+    pub fn use_color(&self) -> bool {
+        self.use_color
+    }
+
+    pub fn show_depth(&self) -> bool {
+        self.show_depth
+    }
+
+    // true once per F12 press
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot_requested)
+    }
+}
@@ -1,62 +1,118 @@
+use crate::{texture_atlas::UvRect, VertexLayout};
+
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    // along the axes of the model, before the rotation
+    pub scale: cgmath::Vector3<f32>,
+    // the part of the texture shown, for textures packed in a TextureAtlas
+    pub uv_rect: UvRect,
+    // multiplies the base color of the materials
+    pub tint: [f32; 4],
+    // not used by the renderer, for custom shaders: instance.custom
+    pub custom: [f32; 4],
 }
 
 // Data that will be used in wgpu::Buffer
 // quaternions don't have WGSL analog
 // so we convert the data into a matrix
+// We need to switch from using a step mode of Vertex to Instance
+// This means that our shaders will only change to use the next
+// instance when the shader starts processing a new instance
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+#[step_mode(Instance)]
 pub struct InstanceRaw {
+    // A mat4 takes up 4 vertex slots as it is technically 4 vec4s: 5 to 8
+    // We'll have to reassemble the mat4 in the shader.
+    // Vertex uses the locations before 5
+    #[location(5)]
     model: [[f32; 4]; 4],
+    #[location(9)]
+    uv_rect: [f32; 4],
+    // turns the normals: the model matrix would bend them
+    // when the instance is not scaled the same on all axes, 10 to 12
+    #[location(10)]
+    normal: [[f32; 3]; 3],
+    #[location(13)]
+    tint: [f32; 4],
+    #[location(14)]
+    custom: [f32; 4],
 }
 
 impl Instance {
-    pub fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
+    // An instance at position, not rotated
+    pub fn new(position: cgmath::Vector3<f32>) -> Self {
+        Self {
+            position,
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            uv_rect: UvRect::FULL,
+            tint: [1.0; 4],
+            custom: [0.0; 4],
         }
     }
-}
 
-impl InstanceRaw {
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
-            // We need to switch from using a step mode of Vertex to Instance
-            // This means that our shaders will only change to use the next
-            // instance when the shader starts processing a new instance
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                // A mat4 takes up 4 vertex slots as it is technically 4 vec4s. We need to define a slot
-                // for each vec4. We'll have to reassemble the mat4 in the shader.
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    // While our vertex shader only uses locations 0, and 1 now, in later tutorials, we'll
-                    // be using 2, 3, and 4, for Vertex. We'll start at slot 5, not conflict with them later
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 7,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 8,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
+    pub fn with_rotation(mut self, rotation: cgmath::Quaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: cgmath::Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_uv_rect(mut self, uv_rect: UvRect) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_custom(mut self, custom: [f32; 4]) -> Self {
+        self.custom = custom;
+        self
+    }
+
+    // scaled, then rotated, then moved to position
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    // The inverse transpose of the model matrix without the translation,
+    // which keeps the normals perpendicular to the surface:
+    // the rotation is kept and the scale is inverted
+    // A flattened axis (scale 0) has no inverse, a tiny scale is used instead:
+    // the normals of the flat instance point along that axis, once normalized
+    pub fn normal_matrix(&self) -> cgmath::Matrix3<f32> {
+        const MIN_SCALE: f32 = 1e-6;
+        let inverse = |scale: f32| 1.0 / scale.abs().max(MIN_SCALE).copysign(scale);
+        let rotation = cgmath::Matrix3::from(self.rotation);
+        cgmath::Matrix3::from_cols(
+            rotation.x * inverse(self.scale.x),
+            rotation.y * inverse(self.scale.y),
+            rotation.z * inverse(self.scale.z),
+        )
+    }
+
+    pub fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: self.model_matrix().into(),
+            uv_rect: [
+                self.uv_rect.offset[0],
+                self.uv_rect.offset[1],
+                self.uv_rect.size[0],
+                self.uv_rect.size[1],
             ],
+            normal: self.normal_matrix().into(),
+            tint: self.tint,
+            custom: self.custom,
         }
     }
 }
@@ -0,0 +1,161 @@
+// The instances of a model and their vertex buffer, which can change
+// while running: instances are added, removed and moved between frames.
+// Only what changed is written to the GPU by update().
+use std::ops::Range;
+
+use bytemuck::Zeroable;
+// for create_buffer_init, use an extension trait
+use wgpu::util::DeviceExt;
+
+use crate::instance::{Instance, InstanceRaw};
+
+pub struct InstanceManager {
+    instances: Vec<Instance>,
+    buffer: wgpu::Buffer,
+    // instances the buffer can hold before it has to grow
+    capacity: usize,
+    // the instances changed since the last update, sorted and not overlapping
+    dirty: Vec<Range<usize>>,
+}
+
+impl InstanceManager {
+    pub fn new(device: &wgpu::Device, instances: Vec<Instance>) -> Self {
+        // an empty buffer can't be bound, keep room for one instance
+        let capacity = instances.len().max(1);
+        let mut instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        instance_data.resize(capacity, InstanceRaw::zeroed());
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&instance_data),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        Self {
+            instances,
+            buffer,
+            capacity,
+            dirty: Vec::new(),
+        }
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The ranges of instances to write at the next update
+    pub fn dirty_ranges(&self) -> &[Range<usize>] {
+        &self.dirty
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // returns the index to use with get_mut, set and remove
+    pub fn add(&mut self, device: &wgpu::Device, instance: Instance) -> usize {
+        self.instances.push(instance);
+        let index = self.instances.len() - 1;
+        if self.instances.len() > self.capacity {
+            // the new buffer is empty, all the instances are written again
+            self.capacity = (self.capacity * 2).max(self.instances.len());
+            self.buffer = Self::create_buffer(device, self.capacity);
+            self.mark_dirty(0..self.instances.len());
+        } else {
+            self.mark_dirty(index..index + 1);
+        }
+        index
+    }
+
+    // the instances after index move down by one
+    pub fn remove(&mut self, index: usize) -> Instance {
+        let instance = self.instances.remove(index);
+        self.mark_dirty(index..self.instances.len());
+        instance
+    }
+
+    // Replace all the instances, the buffer is kept if they fit in it
+    pub fn set_all(&mut self, device: &wgpu::Device, instances: Vec<Instance>) {
+        self.instances = instances;
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        self.dirty.clear();
+        self.mark_dirty(0..self.instances.len());
+    }
+
+    pub fn get(&self, index: usize) -> &Instance {
+        &self.instances[index]
+    }
+
+    // changes are sent to the GPU by update()
+    pub fn get_mut(&mut self, index: usize) -> &mut Instance {
+        self.mark_dirty(index..index + 1);
+        &mut self.instances[index]
+    }
+
+    pub fn set(&mut self, index: usize, instance: Instance) {
+        *self.get_mut(index) = instance;
+    }
+
+    // all the instances are written at the next update
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Instance> {
+        self.mark_dirty(0..self.instances.len());
+        self.instances.iter_mut()
+    }
+
+    // Write the changed instances, one write_buffer per dirty range
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        for range in self.dirty.drain(..) {
+            // removals may have left ranges past the end
+            let range = range.start..range.end.min(self.instances.len());
+            if range.is_empty() {
+                continue;
+            }
+            let instance_data = self.instances[range.clone()]
+                .iter()
+                .map(Instance::to_raw)
+                .collect::<Vec<_>>();
+            queue.write_buffer(
+                &self.buffer,
+                (range.start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&instance_data),
+            );
+        }
+    }
+
+    // Add a range to the dirty ones, merging those overlapping or touching it
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let start = self.dirty.partition_point(|r| r.end < range.start);
+        let end = self.dirty.partition_point(|r| r.start <= range.end);
+        let merged = if start < end {
+            range.start.min(self.dirty[start].start)..range.end.max(self.dirty[end - 1].end)
+        } else {
+            range
+        };
+        self.dirty.splice(start..end, [merged]);
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
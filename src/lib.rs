@@ -1,7 +1,59 @@
-mod camera;
-mod camera_controller;
+// The renderer as a library
+//
+// event_loop::run() opens a window with the demo scene,
+// applications wanting their own geometry can either give a scene
+// to event_loop::run_with(), or drive their own event loop with
+// a Renderer, a Scene and an Input (State glues the three together).
+// the code generated by #[derive(VertexLayout)] names this crate
+extern crate self as learn_wgpu;
+
+pub mod camera;
+pub mod camera_controller;
+pub mod capture;
+pub mod depth_pass;
 pub mod event_loop;
-mod instance;
-mod mytexture;
-mod state;
-mod vertex;
+pub mod input;
+pub mod instance;
+pub mod instance_manager;
+pub mod light;
+pub mod material;
+pub mod model;
+pub mod mytexture;
+pub mod pipeline;
+pub mod preprocessor;
+pub mod reflection;
+pub mod renderer;
+pub mod resources;
+pub mod scene;
+pub mod shader_watcher;
+pub mod shadow;
+pub mod skybox;
+pub mod state;
+pub mod texture_atlas;
+pub mod texture_cache;
+pub mod vertex;
+pub mod vertex_layout;
+
+pub use camera::Camera;
+pub use camera_controller::CameraController;
+pub use input::Input;
+pub use instance::{Instance, InstanceRaw};
+pub use instance_manager::InstanceManager;
+pub use light::{Attenuation, Light, LightKind};
+pub use material::Material;
+pub use model::{Mesh, Model};
+pub use mytexture::{MyTexture, SamplerOptions, TextureOptions};
+pub use pipeline::{PipelineDesc, PipelineRegistry};
+pub use preprocessor::ShaderPreprocessor;
+pub use reflection::ShaderReflection;
+pub use renderer::Renderer;
+pub use scene::{Scene, SceneObject};
+pub use shader_watcher::ShaderWatcher;
+pub use shadow::ShadowSettings;
+pub use state::State;
+pub use texture_atlas::{TextureAtlas, UvRect};
+pub use texture_cache::{TextureCache, TextureHandle};
+pub use vertex::Vertex;
+pub use vertex_layout::VertexLayout;
+// the derive macro, named like the trait it implements
+pub use learn_wgpu_derive::VertexLayout;
@@ -0,0 +1,387 @@
+use cgmath::prelude::*;
+
+use crate::{
+    model::Mesh,
+    mytexture::DEPTH_FORMAT,
+    pipeline::{compile_shader, PassPipeline, PendingPipeline, ShaderPass},
+    preprocessor::{create_builtin_module, ShaderPreprocessor},
+    scene::Scene,
+    vertex::Vertex,
+    vertex_layout::VertexLayout,
+};
+
+// How the light of point and spot lights fades with the distance d:
+// the intensity is divided by constant + linear * d + quadratic * d²
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    // same intensity at any distance
+    pub const NONE: Self = Self::new(1.0, 0.0, 0.0);
+
+    pub const fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Self {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    // infinitely far away, like the sun: only a direction, no attenuation
+    Directional {
+        direction: cgmath::Vector3<f32>,
+    },
+    // shines the same in all directions
+    Point {
+        position: cgmath::Vector3<f32>,
+        attenuation: Attenuation,
+    },
+    // a point light restricted to a cone around direction
+    // full intensity inside inner_cutoff, fading to nothing at outer_cutoff
+    Spot {
+        position: cgmath::Vector3<f32>,
+        direction: cgmath::Vector3<f32>,
+        attenuation: Attenuation,
+        inner_cutoff: cgmath::Deg<f32>,
+        outer_cutoff: cgmath::Deg<f32>,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    // multiplies the color, so it can go over 1.0
+    pub intensity: f32,
+    // only directional and spot lights can cast shadows
+    pub cast_shadows: bool,
+}
+
+// One element of the light storage buffer, the same for all kinds
+// vec3 are aligned on 16 bytes in WGSL, so each is followed by a
+// scalar field filling the 4 bytes left
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightRaw {
+    position: [f32; 3],
+    // one of the LIGHT_* constants of the shaders
+    kind: u32,
+    direction: [f32; 3],
+    intensity: f32,
+    color: [f32; 3],
+    // cosines of the cone angles, cheaper to compare in the shader
+    inner_cutoff: f32,
+    // constant, linear, quadratic
+    attenuation: [f32; 3],
+    outer_cutoff: f32,
+    // layer of the shadow map texture array, -1 without shadows
+    shadow_layer: i32,
+    _padding: [u32; 3],
+}
+
+impl Light {
+    pub fn directional(direction: cgmath::Vector3<f32>, color: [f32; 3]) -> Self {
+        Self {
+            kind: LightKind::Directional { direction },
+            color,
+            intensity: 1.0,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn point(position: cgmath::Vector3<f32>, color: [f32; 3]) -> Self {
+        Self {
+            kind: LightKind::Point {
+                position,
+                attenuation: Attenuation::NONE,
+            },
+            color,
+            intensity: 1.0,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn spot(
+        position: cgmath::Vector3<f32>,
+        direction: cgmath::Vector3<f32>,
+        inner_cutoff: cgmath::Deg<f32>,
+        outer_cutoff: cgmath::Deg<f32>,
+        color: [f32; 3],
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                position,
+                direction,
+                attenuation: Attenuation::NONE,
+                inner_cutoff,
+                outer_cutoff,
+            },
+            color,
+            intensity: 1.0,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // ignored by point lights, see shadow::ShadowPass
+    pub fn with_shadows(mut self) -> Self {
+        self.cast_shadows = true;
+        self
+    }
+
+    // ignored by directional lights
+    pub fn with_attenuation(mut self, new_attenuation: Attenuation) -> Self {
+        match &mut self.kind {
+            LightKind::Directional { .. } => {}
+            LightKind::Point { attenuation, .. } | LightKind::Spot { attenuation, .. } => {
+                *attenuation = new_attenuation
+            }
+        }
+        self
+    }
+
+    // where the light is, directional lights have no position
+    pub fn position(&self) -> Option<cgmath::Vector3<f32>> {
+        match self.kind {
+            LightKind::Directional { .. } => None,
+            LightKind::Point { position, .. } | LightKind::Spot { position, .. } => Some(position),
+        }
+    }
+
+    // shadow_layer is given by the shadow pass to the lights it renders
+    pub fn to_raw(&self, shadow_layer: Option<u32>) -> LightRaw {
+        let mut raw = LightRaw {
+            position: [0.0; 3],
+            kind: 0,
+            direction: [0.0; 3],
+            intensity: self.intensity,
+            color: self.color,
+            inner_cutoff: 0.0,
+            attenuation: [1.0, 0.0, 0.0],
+            outer_cutoff: 0.0,
+            shadow_layer: shadow_layer.map_or(-1, |layer| layer as i32),
+            _padding: [0; 3],
+        };
+        let attenuation_raw = |a: &Attenuation| [a.constant, a.linear, a.quadratic];
+        match &self.kind {
+            LightKind::Directional { direction } => {
+                raw.kind = LIGHT_DIRECTIONAL;
+                raw.direction = direction.normalize().into();
+            }
+            LightKind::Point {
+                position,
+                attenuation,
+            } => {
+                raw.kind = LIGHT_POINT;
+                raw.position = (*position).into();
+                raw.attenuation = attenuation_raw(attenuation);
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                attenuation,
+                inner_cutoff,
+                outer_cutoff,
+            } => {
+                raw.kind = LIGHT_SPOT;
+                raw.position = (*position).into();
+                raw.direction = direction.normalize().into();
+                raw.attenuation = attenuation_raw(attenuation);
+                raw.inner_cutoff = inner_cutoff.cos();
+                raw.outer_cutoff = outer_cutoff.cos();
+            }
+        }
+        raw
+    }
+}
+
+impl Default for Light {
+    // a white point light above and in front of the origin
+    fn default() -> Self {
+        Self::point((2.0, 2.0, 2.0).into(), [1.0, 1.0, 1.0])
+    }
+}
+
+// must match the constants in the shaders
+const LIGHT_DIRECTIONAL: u32 = 0;
+const LIGHT_POINT: u32 = 1;
+const LIGHT_SPOT: u32 = 2;
+
+// The storage buffer starts with the number of lights,
+// padded to 16 bytes as the array that follows is aligned like its vec3
+pub const LIGHT_LIST_HEADER_SIZE: wgpu::BufferAddress = 16;
+
+// Size of a light list buffer able to hold capacity lights
+pub fn light_list_size(capacity: usize) -> wgpu::BufferAddress {
+    LIGHT_LIST_HEADER_SIZE + (capacity * std::mem::size_of::<LightRaw>()) as wgpu::BufferAddress
+}
+
+// Draws a small cube of the light color where each light is,
+// so we can see where we are placing them
+pub struct LightMarker {
+    format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: PassPipeline,
+    cube: Mesh,
+}
+
+impl LightMarker {
+    // half the size of the cube side
+    const SIZE: f32 = 0.1;
+
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let source = include_str!("shaders/light.wgsl");
+        let shader = create_builtin_module(device, "Light Marker Shader", source);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Marker Pipeline Layout"),
+            // the lights are bound with the camera
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, format, &shader);
+
+        Self {
+            format,
+            pipeline_layout,
+            pipeline: PassPipeline::new(pipeline, source),
+            cube: Self::create_cube(device),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Light Marker Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                // one instance per light, the position comes from the light list
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // hidden by the objects in front of it, like the rest of the scene
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    // A cube centered on the origin, 4 vertices per face
+    // so each face has its own normal
+    fn create_cube(device: &wgpu::Device) -> Mesh {
+        // normal, then the axes going right and up when looking at the face
+        const FACES: [[[f32; 3]; 3]; 6] = [
+            [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            [[0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+            [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+            [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+            [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        ];
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for [normal, right, up] in FACES {
+            let first = vertices.len() as u32;
+            // counter clock-wise, starting bottom left
+            for (r, u) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let position =
+                    std::array::from_fn(|i| (normal[i] + r * right[i] + u * up[i]) * Self::SIZE);
+                vertices.push(Vertex::new(position, [0.0, 0.0], normal));
+            }
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        Mesh::new(device, "light marker", &vertices, &indices, 0)
+    }
+
+    // Must be called in a pass with the depth buffer of the scene
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, scene: &Scene) {
+        let num_lights = scene.lights().len() as u32;
+        if num_lights == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(self.pipeline.get());
+        render_pass.set_bind_group(0, scene.camera_bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.cube.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.cube.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.cube.num_indices, 0, 0..num_lights);
+    }
+}
+
+impl ShaderPass for LightMarker {
+    fn shader_file(&self) -> &'static str {
+        "light.wgsl"
+    }
+
+    fn pipeline_mut(&mut self) -> &mut PassPipeline {
+        &mut self.pipeline
+    }
+
+    fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        let (shader, _) = compile_shader(device, preprocessor, "Light Marker Shader", source, &[])?;
+        let pending = PendingPipeline::new(device, || {
+            Self::create_pipeline(device, &self.pipeline_layout, self.format, &shader)
+        });
+        self.pipeline.replace(source, pending);
+        Ok(())
+    }
+}
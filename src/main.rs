@@ -3,10 +3,15 @@
 // As recommended in the Rust Book's best practices
 // here we use the lib crate
 // and we access only the public elements
-use learn_wgpu::event_loop::run;
+use learn_wgpu::event_loop::{run_demo, RunOptions};
 
 fn main() {
+    let mut options = RunOptions::default();
+    // development mode: edits of the shaders show up without restarting
+    if cfg!(debug_assertions) {
+        options = options.with_shader_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders"));
+    }
     // tokio could have been also used
-    // use pollster to await the futures in run
-    pollster::block_on(run());
+    // use pollster to await the futures in run_demo
+    pollster::block_on(run_demo(options));
 }
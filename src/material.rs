@@ -0,0 +1,278 @@
+// How the surface of a mesh looks: the metallic-roughness model of glTF
+use std::sync::Mutex;
+
+// for create_buffer_init, use an extension trait
+use wgpu::util::DeviceExt;
+
+use crate::{mytexture::MyTexture, texture_cache::TextureHandle};
+
+// Which optional textures a material has, must match the constants of the shader
+const HAS_NORMAL_MAP: u32 = 1;
+const HAS_METALLIC_ROUGHNESS_TEXTURE: u32 = 2;
+const HAS_OCCLUSION_TEXTURE: u32 = 4;
+const HAS_EMISSIVE_TEXTURE: u32 = 8;
+
+// The factors of a material, read by the shader next to the textures
+// vec3 are aligned on 16 bytes in WGSL, so emissive is followed by a scalar
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    base_color: [f32; 4],
+    emissive: [f32; 3],
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    // HAS_* bits
+    textures: u32,
+}
+
+// The bind group of a material, with what it was made from
+struct MaterialBindGroup {
+    versions: [(usize, u64); 5],
+    uniform: MaterialUniform,
+    bind_group: wgpu::BindGroup,
+}
+
+// What is bound to the group 0 of the textured pipeline
+// Each texture is multiplied by its factor, a missing texture is like a white one:
+// the factor alone is used.
+pub struct Material {
+    pub name: String,
+    // the color of the surface (or of the reflections for metals), in sRGB
+    pub base_color_texture: TextureHandle,
+    // linear RGBA, alpha is the opacity
+    pub base_color_factor: [f32; 4],
+    // roughness in the green channel and metallic in the blue one, linear
+    pub metallic_roughness_texture: Option<TextureHandle>,
+    // 0.0 for dielectrics (plastic, wood, ...), 1.0 for metals
+    pub metallic_factor: f32,
+    // 0.0 is a perfect mirror, 1.0 reflects the light in all directions
+    pub roughness_factor: f32,
+    // tangent space normals, in a linear texture (see TextureOptions::linear)
+    pub normal_texture: Option<TextureHandle>,
+    // how much the normal map tilts the normals
+    pub normal_scale: f32,
+    // how much of the ambient light reaches the surface, in the red channel
+    pub occlusion_texture: Option<TextureHandle>,
+    pub occlusion_strength: f32,
+    // light given off by the surface, in sRGB
+    pub emissive_texture: Option<TextureHandle>,
+    // linear RGB, black by default: no light
+    pub emissive_factor: [f32; 3],
+    // kept to build the bind group when the material changes
+    device: wgpu::Device,
+    layout: wgpu::BindGroupLayout,
+    bind_group: Mutex<Option<MaterialBindGroup>>,
+}
+
+impl Material {
+    // A rough dielectric showing base_color_texture
+    // layout is Renderer::material_bind_group_layout
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: &str,
+        base_color_texture: MyTexture,
+    ) -> Self {
+        Self::from_handle(device, layout, name, TextureHandle::new(base_color_texture))
+    }
+
+    // A material showing a texture of the cache (or of another material)
+    pub fn from_handle(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: &str,
+        base_color_texture: TextureHandle,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            base_color_texture,
+            base_color_factor: [1.0; 4],
+            metallic_roughness_texture: None,
+            metallic_factor: 0.0,
+            roughness_factor: 0.5,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_texture: None,
+            emissive_factor: [0.0; 3],
+            device: device.clone(),
+            layout: layout.clone(),
+            bind_group: Mutex::new(None),
+        }
+    }
+
+    pub fn with_base_color_factor(mut self, base_color_factor: [f32; 4]) -> Self {
+        self.base_color_factor = base_color_factor;
+        self
+    }
+
+    pub fn with_metallic_roughness(mut self, metallic: f32, roughness: f32) -> Self {
+        self.metallic_factor = metallic;
+        self.roughness_factor = roughness;
+        self
+    }
+
+    // the factors of with_metallic_roughness multiply the texture
+    pub fn with_metallic_roughness_texture(mut self, texture: TextureHandle) -> Self {
+        self.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal_texture: TextureHandle) -> Self {
+        self.normal_texture = Some(normal_texture);
+        self
+    }
+
+    pub fn with_occlusion_texture(mut self, texture: TextureHandle, strength: f32) -> Self {
+        self.occlusion_texture = Some(texture);
+        self.occlusion_strength = strength;
+        self
+    }
+
+    // without texture, the whole surface gives off emissive_factor
+    pub fn with_emissive(mut self, emissive_factor: [f32; 3]) -> Self {
+        self.emissive_factor = emissive_factor;
+        self
+    }
+
+    pub fn with_emissive_texture(mut self, texture: TextureHandle) -> Self {
+        self.emissive_texture = Some(texture);
+        self
+    }
+
+    // The bind group of the current textures and factors, which can change at any time:
+    // it is created the first time, and again when something changed
+    pub fn bind_group(&self) -> wgpu::BindGroup {
+        let version = |texture: &Option<TextureHandle>| {
+            texture.as_ref().map_or((0, 0), TextureHandle::version)
+        };
+        let versions = [
+            self.base_color_texture.version(),
+            version(&self.metallic_roughness_texture),
+            version(&self.normal_texture),
+            version(&self.occlusion_texture),
+            version(&self.emissive_texture),
+        ];
+        let uniform = self.uniform();
+
+        let mut cached = self.bind_group.lock().unwrap();
+        match &*cached {
+            Some(cached) if cached.versions == versions && cached.uniform == uniform => {
+                cached.bind_group.clone()
+            }
+            _ => {
+                let bind_group = self.create_bind_group(&uniform);
+                *cached = Some(MaterialBindGroup {
+                    versions,
+                    uniform,
+                    bind_group: bind_group.clone(),
+                });
+                bind_group
+            }
+        }
+    }
+
+    fn uniform(&self) -> MaterialUniform {
+        let mut textures = 0;
+        for (texture, bit) in [
+            (&self.normal_texture, HAS_NORMAL_MAP),
+            (
+                &self.metallic_roughness_texture,
+                HAS_METALLIC_ROUGHNESS_TEXTURE,
+            ),
+            (&self.occlusion_texture, HAS_OCCLUSION_TEXTURE),
+            (&self.emissive_texture, HAS_EMISSIVE_TEXTURE),
+        ] {
+            if texture.is_some() {
+                textures |= bit;
+            }
+        }
+        MaterialUniform {
+            base_color: self.base_color_factor,
+            emissive: self.emissive_factor,
+            metallic: self.metallic_factor,
+            roughness: self.roughness_factor,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            textures,
+        }
+    }
+
+    fn create_bind_group(&self, uniform: &MaterialUniform) -> wgpu::BindGroup {
+        let base_color_texture = self.base_color_texture.texture();
+        // a missing texture is replaced by the base color one in the bind group
+        // (the layout needs a texture there) but it is not used by the shader
+        let texture = |texture: &Option<TextureHandle>| {
+            texture
+                .as_ref()
+                .map_or_else(|| base_color_texture.clone(), TextureHandle::texture)
+        };
+        let textures = [
+            base_color_texture.clone(),
+            texture(&self.normal_texture),
+            texture(&self.metallic_roughness_texture),
+            texture(&self.occlusion_texture),
+            texture(&self.emissive_texture),
+        ];
+        let uniform_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Material Buffer", self.name)),
+                contents: bytemuck::cast_slice(&[*uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        // This may seem not very DRY
+        // BindGroup is a more specific declaration of the bind group layout
+        // this pattern allows us to swap BindGroups on the fly as long as they have the same layout
+        // The uniform is at binding 0, then each texture is followed by its sampler:
+        // base color, normal, metallic-roughness, occlusion and emissive
+        // (the layout is read from the shader, see Renderer::material_bind_group_layout)
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        for (i, texture) in textures.iter().enumerate() {
+            let binding = 1 + 2 * i as u32;
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &entries,
+            label: Some("material_bind_group"),
+        })
+    }
+}
+
+impl Clone for Material {
+    // the clone builds its own bind group, its textures may be changed
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            base_color_texture: self.base_color_texture.clone(),
+            base_color_factor: self.base_color_factor,
+            metallic_roughness_texture: self.metallic_roughness_texture.clone(),
+            metallic_factor: self.metallic_factor,
+            roughness_factor: self.roughness_factor,
+            normal_texture: self.normal_texture.clone(),
+            normal_scale: self.normal_scale,
+            occlusion_texture: self.occlusion_texture.clone(),
+            occlusion_strength: self.occlusion_strength,
+            emissive_texture: self.emissive_texture.clone(),
+            emissive_factor: self.emissive_factor,
+            device: self.device.clone(),
+            layout: self.layout.clone(),
+            bind_group: Mutex::new(None),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// for create_buffer_init, use an extension trait
+use wgpu::util::DeviceExt;
+
+use crate::{
+    material::Material,
+    vertex::{fill_missing_tangents, Vertex},
+};
+
+// Geometry uploaded to the GPU
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+    // index of the material in Model::materials
+    pub material: usize,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+        material: usize,
+    ) -> Self {
+        // for the normal maps, see vertex::compute_tangents
+        let mut with_tangents;
+        let vertices = if vertices.iter().any(|v| v.tangent() == [0.0; 4]) {
+            with_tangents = vertices.to_vec();
+            fill_missing_tangents(&mut with_tangents, indices);
+            &with_tangents[..]
+        } else {
+            vertices
+        };
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{name} Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{name} Index Buffer")),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            material,
+        }
+    }
+}
+
+// Meshes sharing a list of materials, like the content of an OBJ file
+// Mesh::material is an index in materials
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
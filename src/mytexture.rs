@@ -1,11 +1,227 @@
+#[derive(Clone)]
 pub struct MyTexture {
-    pub diffuse_texture_view: wgpu::TextureView,
-    pub diffuse_sampler: wgpu::Sampler,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
 }
 
+// How a texture is sampled
+// far from the camera, many texels fall in one pixel: the mipmaps
+// (smaller and smaller copies of the texture) avoid picking one of them at random
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerOptions {
+    // what to do with texture coordinates outside of [0, 1]:
+    // ClampToEdge repeats the border texels, Repeat tiles the texture
+    // and MirrorRepeat tiles it flipping every other copy
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    // when a texel covers several pixels (close to the camera)
+    pub mag_filter: wgpu::FilterMode,
+    // when several texels fall in one pixel (far from the camera)
+    pub min_filter: wgpu::FilterMode,
+    // Nearest uses the closest mip level,
+    // Linear blends the two closest ones (trilinear filtering)
+    pub mipmap_filter: wgpu::FilterMode,
+    // textures seen at a grazing angle get blurry with mipmaps alone,
+    // anisotropic filtering takes up to this number of samples along the slope
+    // 1 disables it, the maximum is 16
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            // going from a mip level to the next one would show otherwise
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    // linear filtering everywhere, including between mip levels
+    pub fn trilinear() -> Self {
+        Self::default().with_filters(wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+    }
+
+    // the same mode for all the directions
+    pub fn with_address_mode(self, address_mode: wgpu::AddressMode) -> Self {
+        self.with_address_modes(address_mode, address_mode, address_mode)
+    }
+
+    pub fn with_address_modes(
+        mut self,
+        u: wgpu::AddressMode,
+        v: wgpu::AddressMode,
+        w: wgpu::AddressMode,
+    ) -> Self {
+        self.address_mode_u = u;
+        self.address_mode_v = v;
+        self.address_mode_w = w;
+        self
+    }
+
+    // min also applies between mip levels
+    pub fn with_filters(mut self, mag: wgpu::FilterMode, min: wgpu::FilterMode) -> Self {
+        self.mag_filter = mag;
+        self.min_filter = min;
+        self.mipmap_filter = min;
+        self
+    }
+
+    pub fn with_mipmap_filter(mut self, mipmap_filter: wgpu::FilterMode) -> Self {
+        self.mipmap_filter = mipmap_filter;
+        self
+    }
+
+    // anisotropic filtering needs linear filtering everywhere,
+    // so this also switches to trilinear filtering
+    pub fn with_anisotropy(self, anisotropy: u16) -> Self {
+        let mut options = self.with_filters(wgpu::FilterMode::Linear, wgpu::FilterMode::Linear);
+        options.anisotropy = anisotropy.clamp(1, 16);
+        options
+    }
+
+    fn create_sampler(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+        // wgpu refuses to create the sampler otherwise
+        let anisotropy = if self.anisotropy > 1 && !all_linear {
+            log::warn!("Anisotropic filtering needs linear filters, disabling it");
+            1
+        } else {
+            self.anisotropy
+        };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: anisotropy,
+            ..Default::default()
+        })
+    }
+}
+
+// How a texture is created from an image
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureOptions {
+    // shown by the graphics debuggers and in the wgpu errors
+    pub label: Option<String>,
+    // colors are usually stored in sRGB, and converted to linear values
+    // when sampled; normal maps and other data textures must not be converted
+    pub srgb: bool,
+    pub sampler: SamplerOptions,
+    // the number of mip levels is limited to this, None for the full chain
+    pub max_mip_levels: Option<u32>,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            label: None,
+            srgb: true,
+            sampler: SamplerOptions::default(),
+            max_mip_levels: None,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    // for data textures, like normal maps
+    pub fn linear(mut self) -> Self {
+        self.srgb = false;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerOptions) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // at least 1, the image itself
+    pub fn with_max_mip_levels(mut self, max_mip_levels: u32) -> Self {
+        self.max_mip_levels = Some(max_mip_levels.max(1));
+        self
+    }
+
+    fn mip_level_count(&self, width: u32, height: u32) -> u32 {
+        let count = mip_level_count(width, height);
+        self.max_mip_levels.map_or(count, |max| count.min(max))
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        if self.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+}
+
+// Cubemaps made from HDR panoramas, half floats are filterable everywhere
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Depth32Float is precise enough for our scenes and has no stencil
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 impl MyTexture {
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, diffuse_bytes: &[u8]) -> Self {
-        let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap();
+    // An sRGB texture from the bytes of an image file (PNG or JPEG)
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        diffuse_bytes: &[u8],
+    ) -> image::ImageResult<Self> {
+        Self::from_bytes(device, queue, diffuse_bytes, &TextureOptions::default())
+    }
+
+    // fails if the bytes can't be decoded
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        diffuse_bytes: &[u8],
+        options: &TextureOptions,
+    ) -> image::ImageResult<Self> {
+        let diffuse_image = image::load_from_memory(diffuse_bytes)?;
+        Ok(Self::from_image_with(
+            device,
+            queue,
+            &diffuse_image,
+            options,
+        ))
+    }
+
+    // For images already decoded (or generated)
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        diffuse_image: &image::DynamicImage,
+    ) -> Self {
+        Self::from_image_with(device, queue, diffuse_image, &TextureOptions::default())
+    }
+
+    pub fn from_image_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        diffuse_image: &image::DynamicImage,
+        options: &TextureOptions,
+    ) -> Self {
         let diffuse_rgba = diffuse_image.to_rgba8();
 
         use image::GenericImageView;
@@ -20,15 +236,17 @@ impl MyTexture {
         };
         let diffuse_texture = device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1, // We'll talk about this a little later
+            // the full chain, down to 1x1, unless the options limit it
+            mip_level_count: options.mip_level_count(dimensions.0, dimensions.1),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             // Most images are stored using sRGB, so we need to reflect that here.
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            // (data textures are not, see TextureOptions::linear)
+            format: options.format(),
             // TEXTURE_BINDING tells wgpu that we want to use this texture in shaders
             // COPY_DST means that we want to copy data to this texture
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some("diffuse_texture"),
+            label: Some(options.label.as_deref().unwrap_or("diffuse_texture")),
             // This is the same as with the SurfaceConfig. It
             // specifies what texture formats can be used to
             // create TextureViews for this texture. The base
@@ -40,48 +258,405 @@ impl MyTexture {
         });
 
         // The Texture struct has no methods to interact with the data directly
-        queue.write_texture(
-            // Tells wgpu where to copy the pixel data
-            wgpu::TexelCopyTextureInfo {
-                texture: &diffuse_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            // The actual pixel data
-            &diffuse_rgba,
-            // The layout of the texture
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            texture_size,
-        );
+        // each mip level is half the size of the previous one, computed on the CPU
+        let mip_level_count = diffuse_texture.mip_level_count() as usize;
+        for (mip_level, level_image) in rgba8_mip_chain(diffuse_rgba, options.srgb)
+            .into_iter()
+            .take(mip_level_count)
+            .enumerate()
+        {
+            let (width, height) = level_image.dimensions();
+            queue.write_texture(
+                // Tells wgpu where to copy the pixel data
+                wgpu::TexelCopyTextureInfo {
+                    texture: &diffuse_texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                // The actual pixel data
+                &level_image,
+                // The layout of the texture
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         // We don't need to configure the texture view much, so let's
         // let wgpu define it.
         let diffuse_texture_view =
             diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let diffuse_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            // what to do if the sample gets a texture coordinate
-            // which is out of the texture itself
+        let diffuse_sampler = options
+            .sampler
+            .create_sampler(device, options.label.as_deref());
+
+        Self {
+            texture: diffuse_texture,
+            view: diffuse_texture_view,
+            sampler: diffuse_sampler,
+        }
+    }
+
+    // A cubemap from its six faces, in the order of the layers of wgpu:
+    // +X, -X, +Y, -Y, +Z, -Z (right, left, top, bottom, front, back)
+    // The faces are square and the same size, they are resized to the first one otherwise.
+    pub fn cube_from_faces(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage; 6],
+        options: &TextureOptions,
+    ) -> Self {
+        let size = faces[0].width();
+        let levels = faces
+            .iter()
+            .map(|face| {
+                let mut rgba = face.to_rgba8();
+                if rgba.dimensions() != (size, size) {
+                    rgba = image::imageops::resize(
+                        &rgba,
+                        size,
+                        size,
+                        image::imageops::FilterType::Triangle,
+                    );
+                }
+                rgba8_mip_chain(rgba, options.srgb)
+                    .into_iter()
+                    .take(options.mip_level_count(size, size) as usize)
+                    .map(|level| level.into_raw())
+                    .collect()
+            })
+            .collect();
+        Self::create_cube(device, queue, size, options.format(), levels, options)
+    }
+
+    // A cubemap from a panorama covering all the directions (like the .hdr
+    // environment maps): the longitude goes along x and the latitude along y.
+    // HDR images keep their values above 1.0 in a float texture,
+    // other images are converted from sRGB to linear values.
+    pub fn cube_from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        panorama: &image::DynamicImage,
+        face_size: u32,
+        options: &TextureOptions,
+    ) -> Self {
+        let panorama = linear_rgba32f(panorama);
+        let levels = (0..6)
+            .map(|face| {
+                let face_image = image::Rgba32FImage::from_fn(face_size, face_size, |x, y| {
+                    // from the texel to [-1, 1], then to the direction it is seen from
+                    let u = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let v = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    sample_equirectangular(&panorama, cube_direction(face, u, v))
+                });
+                mip_chain(face_image)
+                    .into_iter()
+                    .take(options.mip_level_count(face_size, face_size) as usize)
+                    .map(|level| {
+                        level
+                            .into_raw()
+                            .into_iter()
+                            .flat_map(|c| f32_to_f16(c).to_le_bytes())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        Self::create_cube(device, queue, face_size, HDR_FORMAT, levels, options)
+    }
+
+    // A 1x1 cubemap of one linear color, the same light from everywhere
+    pub fn uniform_cube(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4]) -> Self {
+        let levels = (0..6).map(|_| vec![color.to_vec()]).collect();
+        Self::create_cube(
+            device,
+            queue,
+            1,
+            wgpu::TextureFormat::Rgba8Unorm,
+            levels,
+            &TextureOptions::default().with_label("uniform cube"),
+        )
+    }
+
+    // levels[face][mip_level] are the texels of each face
+    fn create_cube(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        format: wgpu::TextureFormat,
+        levels: Vec<Vec<Vec<u8>>>,
+        options: &TextureOptions,
+    ) -> Self {
+        let mip_level_count = levels[0].len() as u32;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(options.label.as_deref().unwrap_or("cube_texture")),
+            // a cubemap is a 2D texture with 6 layers
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let bytes_per_texel = format.block_copy_size(None).unwrap();
+        for (face, face_levels) in levels.iter().enumerate() {
+            for (mip_level, texels) in face_levels.iter().enumerate() {
+                let level_size = (size >> mip_level).max(1);
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level: mip_level as u32,
+                        // the layer is the z of the origin
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: face as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    texels,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(bytes_per_texel * level_size),
+                        rows_per_image: Some(level_size),
+                    },
+                    wgpu::Extent3d {
+                        width: level_size,
+                        height: level_size,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        // sampled with a direction instead of texture coordinates
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("cube_texture_view"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = options
+            .sampler
+            .create_sampler(device, options.label.as_deref());
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // The depth buffer: stores the depth of the nearest fragment drawn so far
+    // for each pixel, so it has to be the size of the surface
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            // we render to it, and read it back to visualize it
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // only needed if we want to sample it with a comparison
+        // (textureSampleCompare) like for shadows
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            // what to do when the sample footprint is smaller or larger
-            // than one texel (usaually far from or close to the camera)
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            // mipmaps will be seen later
+            min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
             ..Default::default()
         });
 
         Self {
-            diffuse_texture_view,
-            diffuse_sampler,
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
+
+// 1 + the number of times the largest side can be halved
+// e.g. 256x128 gives 9 levels: 256x128, 128x64, ..., 2x1, 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// The image and its mip levels, each half the size of the previous one
+fn mip_chain<P>(
+    image: image::ImageBuffer<P, Vec<P::Subpixel>>,
+) -> Vec<image::ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: image::Pixel + 'static,
+{
+    let levels = mip_level_count(image.width(), image.height());
+    let mut chain = vec![image];
+    for _ in 1..levels {
+        let previous = chain.last().unwrap();
+        let next = image::imageops::resize(
+            previous,
+            (previous.width() / 2).max(1),
+            (previous.height() / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+        chain.push(next);
+    }
+    chain
+}
+
+// The mip chain of an 8 bits image
+// sRGB values are not proportional to the light: averaging them directly
+// would make the smaller levels darker, so they are filtered as linear values
+pub fn rgba8_mip_chain(image: image::RgbaImage, srgb: bool) -> Vec<image::RgbaImage> {
+    if !srgb {
+        return mip_chain(image);
+    }
+    let linear = image::Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0.map(|c| c as f32 / 255.0);
+        image::Rgba([srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a])
+    });
+    // the first level is the image itself, kept exact
+    let mut chain = vec![image];
+    chain.extend(mip_chain(linear).into_iter().skip(1).map(|level| {
+        image::RgbaImage::from_fn(level.width(), level.height(), |x, y| {
+            let [r, g, b, a] = level.get_pixel(x, y).0;
+            let [r, g, b] = [r, g, b].map(linear_to_srgb);
+            image::Rgba([r, g, b, a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        })
+    }));
+    chain
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Float images are already linear, 8 bits images are sRGB
+fn linear_rgba32f(image: &image::DynamicImage) -> image::Rgba32FImage {
+    let mut rgba = image.to_rgba32f();
+    let is_float = matches!(
+        image,
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+    );
+    if !is_float {
+        for pixel in rgba.pixels_mut() {
+            for c in &mut pixel.0[..3] {
+                *c = srgb_to_linear(*c);
+            }
         }
     }
+    rgba
+}
+
+// Direction of the texel at (u, v) in [-1, 1] of a cubemap face,
+// following the convention of the GPUs for the orientation of the faces
+fn cube_direction(face: usize, u: f32, v: f32) -> cgmath::Vector3<f32> {
+    match face {
+        0 => (1.0, -v, -u),
+        1 => (-1.0, -v, u),
+        2 => (u, 1.0, v),
+        3 => (u, -1.0, -v),
+        4 => (u, -v, 1.0),
+        _ => (-u, -v, -1.0),
+    }
+    .into()
+}
+
+// Bilinear sample of the panorama in a direction
+fn sample_equirectangular(
+    panorama: &image::Rgba32FImage,
+    direction: cgmath::Vector3<f32>,
+) -> image::Rgba<f32> {
+    use std::f32::consts::PI;
+    let direction = cgmath::InnerSpace::normalize(direction);
+    let (width, height) = panorama.dimensions();
+    // longitude from -pi to pi, latitude from the top (0) to the bottom (pi)
+    let longitude = direction.z.atan2(direction.x);
+    let latitude = direction.y.clamp(-1.0, 1.0).acos();
+    let x = (longitude / (2.0 * PI) + 0.5) * width as f32 - 0.5;
+    let y = latitude / PI * height as f32 - 0.5;
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    // wraps around horizontally, clamped at the poles
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as i64).clamp(0, height as i64 - 1) as u32;
+        panorama.get_pixel(x, y).0
+    };
+    let (a, b) = (texel(x0, y0), texel(x0 + 1.0, y0));
+    let (c, d) = (texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0));
+    image::Rgba(std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    }))
+}
+
+// Bits of the closest half float, for the Rgba16Float textures
+// (too small values become 0, too big ones the largest half float)
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let value = value.abs();
+    if value.is_nan() {
+        return sign | 0x7e00;
+    }
+    if value < 6.104e-5 {
+        return sign;
+    }
+    if value >= 65504.0 {
+        return sign | 0x7bff;
+    }
+    // rebias the exponent from 127 to 15, and round the mantissa from 23 to 10 bits
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) as i32 - 127 + 15) as u32;
+    let mantissa = bits & 0x7f_ffff;
+    let rounded = ((exponent << 10) | (mantissa >> 13)) + ((mantissa >> 12) & 1);
+    sign | rounded.min(0x7bff) as u16
 }
@@ -0,0 +1,547 @@
+// Render pipelines built from a short description instead of a full
+// RenderPipelineDescriptor, and kept once built
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+use anyhow::Context;
+
+use crate::{
+    mytexture::DEPTH_FORMAT, preprocessor::ShaderPreprocessor, reflection::ShaderReflection,
+};
+
+// What makes two pipelines of a registry different
+// The bind group layouts and the color target are the ones of the registry.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+    // name given to PipelineRegistry::add_shader,
+    // its entry points are vs_main and fs_main
+    pub shader: String,
+    // the variant of the shader: names defined for its #ifdef
+    pub defines: BTreeSet<String>,
+    // the vertex buffers, in the order of their slots
+    pub vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    pub blend: Option<wgpu::BlendState>,
+    pub cull_mode: Option<wgpu::Face>,
+    pub topology: wgpu::PrimitiveTopology,
+    // None for pipelines drawn without depth buffer
+    pub depth: Option<wgpu::DepthStencilState>,
+}
+
+impl PipelineDesc {
+    // Opaque triangles, back faces culled, hidden by what is nearer
+    pub fn new(shader: &str) -> Self {
+        Self {
+            shader: shader.to_string(),
+            defines: BTreeSet::new(),
+            vertex_layouts: Vec::new(),
+            blend: Some(wgpu::BlendState::REPLACE),
+            cull_mode: Some(wgpu::Face::Back),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                // fragments are kept only if they are nearer than
+                // what has already been drawn (Less)
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+        }
+    }
+
+    pub fn with_define(mut self, name: &str) -> Self {
+        self.defines.insert(name.to_string());
+        self
+    }
+
+    pub fn with_vertex_layouts(
+        mut self,
+        vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    ) -> Self {
+        self.vertex_layouts = vertex_layouts;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: Option<wgpu::DepthStencilState>) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+// The source of a shader, generation is incremented each time it is replaced
+struct Shader {
+    source: String,
+    generation: u64,
+}
+
+// a shader name and its defines
+type Variant = (String, BTreeSet<String>);
+
+// A variant of a shader, ready to be used by pipelines
+#[derive(Clone)]
+struct CompiledShader {
+    module: wgpu::ShaderModule,
+    // to check the vertex buffers of the pipelines
+    reflection: Arc<ShaderReflection>,
+}
+
+// A built pipeline, with the generation of the shader it was built from
+struct CachedPipeline {
+    pipeline: wgpu::RenderPipeline,
+    generation: u64,
+    // built from a replaced shader, used once the device accepted it
+    pending: Option<PendingPipeline>,
+}
+
+// The pipelines sharing a pipeline layout and a color format,
+// created the first time they are asked for
+pub struct PipelineRegistry {
+    device: wgpu::Device,
+    layout: wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    preprocessor: ShaderPreprocessor,
+    shaders: HashMap<String, Shader>,
+    // behind Mutexes so drawing only needs a shared reference
+    // the compiled variants of the current shaders
+    modules: Mutex<HashMap<Variant, CompiledShader>>,
+    pipelines: Mutex<HashMap<PipelineDesc, CachedPipeline>>,
+}
+
+impl PipelineRegistry {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            device: device.clone(),
+            layout: layout.clone(),
+            format,
+            preprocessor: ShaderPreprocessor::new(),
+            shaders: HashMap::new(),
+            modules: Mutex::new(HashMap::new()),
+            pipelines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Add a WGSL shader for the pipelines naming it, see ShaderPreprocessor
+    // for the directives it can use
+    // Adding a shader again replaces it, its pipelines are built again
+    // the next time they are used.
+    // A shader which doesn't compile is an error (with the message of naga,
+    // the WGSL compiler of wgpu) and the previous one is kept. The variants
+    // already used are checked, or the one without defines for a new shader.
+    pub fn add_shader(&mut self, name: &str, source: &str) -> anyhow::Result<()> {
+        let compiled = self.compile_variants(name, source)?;
+        self.replace_shader(name, source, compiled);
+        Ok(())
+    }
+
+    // true when a shader was added with this name
+    pub fn has_shader(&self, name: &str) -> bool {
+        self.shaders.contains_key(name)
+    }
+
+    // Add a snippet for the #include of the shaders, or replace it
+    // The shaders are compiled again with it: if one of them doesn't compile
+    // anymore, it is an error and the previous snippet and shaders are kept.
+    pub fn add_include(&mut self, name: &str, source: &str) -> anyhow::Result<()> {
+        let previous = self.preprocessor.clone();
+        self.preprocessor.add_include(name, source);
+        // all compiled before any is replaced, so the registry is never half updated
+        let compiled = self
+            .shaders
+            .iter()
+            .map(|(shader, Shader { source, .. })| {
+                let compiled = self
+                    .compile_variants(shader, source)
+                    .with_context(|| format!("with {name}, {shader} doesn't compile"))?;
+                Ok((shader.clone(), source.clone(), compiled))
+            })
+            .collect::<anyhow::Result<Vec<_>>>();
+        let compiled = match compiled {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                self.preprocessor = previous;
+                return Err(e);
+            }
+        };
+        for (shader, source, compiled) in compiled {
+            self.replace_shader(&shader, &source, compiled);
+        }
+        Ok(())
+    }
+
+    pub fn has_include(&self, name: &str) -> bool {
+        self.preprocessor.has_include(name)
+    }
+
+    // with the snippets added so far, for the shaders of the other passes
+    pub fn preprocessor(&self) -> &ShaderPreprocessor {
+        &self.preprocessor
+    }
+
+    // The pipeline of desc, built if it wasn't yet or if its shader changed
+    // When a pipeline can't be built again from a replaced shader (its inputs
+    // no longer match the vertex layouts, ...), the error is logged and
+    // the last pipeline built is kept. It is also kept until the device has
+    // validated the new one, which may take a frame or more.
+    // Panics if its shader was not added or if it never could be built.
+    pub fn get(&self, desc: &PipelineDesc) -> wgpu::RenderPipeline {
+        let shader = self
+            .shaders
+            .get(&desc.shader)
+            .unwrap_or_else(|| panic!("no shader named {}", desc.shader));
+
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if let Some(cached) = pipelines.get_mut(desc) {
+            if cached.generation != shader.generation {
+                // not tried again until the shader changes
+                cached.generation = shader.generation;
+                match self.build(desc, shader) {
+                    Ok(pending) => cached.pending = Some(pending),
+                    Err(e) => log::error!("Keeping the previous {} pipeline: {e:#}", desc.shader),
+                }
+            }
+            if let Some(Poll::Ready(result)) = cached.pending.as_mut().map(PendingPipeline::poll) {
+                cached.pending = None;
+                match result {
+                    Ok(pipeline) => cached.pipeline = pipeline,
+                    Err(e) => log::error!("Keeping the previous {} pipeline: {e:#}", desc.shader),
+                }
+            }
+            return cached.pipeline.clone();
+        }
+
+        // nothing to fall back on: used right away, and if the device only
+        // reports an error later, it is logged then
+        let mut pending = self.build(desc, shader).unwrap_or_else(|e| panic!("{e:#}"));
+        let pipeline = match pending.poll() {
+            Poll::Ready(result) => result.unwrap_or_else(|e| panic!("{e:#}")),
+            Poll::Pending => pending.pipeline.clone(),
+        };
+        pipelines.insert(
+            desc.clone(),
+            CachedPipeline {
+                pipeline: pipeline.clone(),
+                generation: shader.generation,
+                pending: pending.is_pending().then_some(pending),
+            },
+        );
+        pipeline
+    }
+
+    // number of pipelines built so far
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the pipeline of desc, compiling its variant of the shader if needed
+    fn build(&self, desc: &PipelineDesc, shader: &Shader) -> anyhow::Result<PendingPipeline> {
+        let variant = (desc.shader.clone(), desc.defines.clone());
+        let mut modules = self.modules.lock().unwrap();
+        let compiled = match modules.get(&variant) {
+            Some(compiled) => compiled.clone(),
+            None => {
+                let compiled = self.compile(&desc.shader, &shader.source, &desc.defines)?;
+                modules.insert(variant, compiled.clone());
+                compiled
+            }
+        };
+        // clearer than the validation error of wgpu
+        compiled
+            .reflection
+            .check_vertex_layouts("vs_main", &desc.vertex_layouts)
+            .with_context(|| format!("{} doesn't match its vertex buffers", desc.shader))?;
+        Ok(PendingPipeline::new(&self.device, || {
+            self.create_pipeline(desc, &compiled.module)
+        }))
+    }
+
+    // The variants already used of a shader compiled from source,
+    // or the one without defines for a new shader
+    fn compile_variants(
+        &self,
+        name: &str,
+        source: &str,
+    ) -> anyhow::Result<Vec<(Variant, CompiledShader)>> {
+        let mut variants = self
+            .pipelines
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|desc| desc.shader == name)
+            .map(|desc| desc.defines.clone())
+            .collect::<BTreeSet<_>>();
+        if variants.is_empty() {
+            variants.insert(BTreeSet::new());
+        }
+        variants
+            .into_iter()
+            .map(|defines| {
+                let compiled = self.compile(name, source, &defines)?;
+                Ok(((name.to_string(), defines), compiled))
+            })
+            .collect()
+    }
+
+    // Swap in the compiled variants, the pipelines are built again when next used
+    fn replace_shader(
+        &mut self,
+        name: &str,
+        source: &str,
+        compiled: Vec<(Variant, CompiledShader)>,
+    ) {
+        let mut modules = self.modules.lock().unwrap();
+        modules.retain(|(shader, _), _| shader != name);
+        modules.extend(compiled);
+        let generation = self.shaders.get(name).map_or(0, |s| s.generation + 1);
+        self.shaders.insert(
+            name.to_string(),
+            Shader {
+                source: source.to_string(),
+                generation,
+            },
+        );
+    }
+
+    fn compile(
+        &self,
+        name: &str,
+        source: &str,
+        defines: &BTreeSet<String>,
+    ) -> anyhow::Result<CompiledShader> {
+        let defines = defines.iter().map(String::as_str).collect::<Vec<_>>();
+        let (module, reflection) =
+            compile_shader(&self.device, &self.preprocessor, name, source, &defines)?;
+        Ok(CompiledShader {
+            module,
+            reflection: Arc::new(reflection),
+        })
+    }
+
+    fn create_pipeline(
+        &self,
+        desc: &PipelineDesc,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        self.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("{} Pipeline", desc.shader)),
+                layout: Some(&self.layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    // what type of vertices we want to pass to the vertex shader
+                    buffers: &desc.vertex_layouts,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                // fragment is optional so it's in an Option
+                // we need it as we want to store color data on the surface
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    // what color output it should set up
+                    // currently we only need one for the surface
+                    targets: &[Some(wgpu::ColorTargetState {
+                        // use the surface's format so copying is easy
+                        format: self.format,
+                        blend: desc.blend,
+                        // write all colors: rgb and alpha
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: desc.topology,
+                    strip_index_format: None,
+                    // front facing triangles are when vertices are given
+                    // in counter clock-wise order
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: desc.cull_mode,
+                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                depth_stencil: desc.depth.clone(),
+                multisample: wgpu::MultisampleState {
+                    // only one sample
+                    count: 1,
+                    // which sample will be active (all of them, i.e one)
+                    mask: !0,
+                    // anti-aliasing related
+                    alpha_to_coverage_enabled: false,
+                },
+                // we will not render to array textures
+                multiview: None,
+                // cache shader compilation data. TODO: why "only really useful for Android build target" ?
+                cache: None,
+            })
+    }
+}
+
+// A pipeline just created, and the errors of its creation which the device
+// reports asynchronously: waiting for them would stall the frame (and never
+// return on the web), so they are polled instead, once per frame
+pub(crate) struct PendingPipeline {
+    pipeline: wgpu::RenderPipeline,
+    errors: Option<Pin<Box<dyn Future<Output = Option<wgpu::Error>>>>>,
+}
+
+impl PendingPipeline {
+    // Run create, its validation errors are caught instead of
+    // the panic of the default error handler of wgpu
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        create: impl FnOnce() -> wgpu::RenderPipeline,
+    ) -> Self {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create();
+        Self {
+            pipeline,
+            errors: Some(Box::pin(device.pop_error_scope())),
+        }
+    }
+
+    // Ready with the pipeline once the device accepted it, without waiting
+    // Polling again after that is pending forever.
+    pub(crate) fn poll(&mut self) -> Poll<anyhow::Result<wgpu::RenderPipeline>> {
+        let Some(errors) = &mut self.errors else {
+            return Poll::Pending;
+        };
+        let poll = errors
+            .as_mut()
+            .poll(&mut std::task::Context::from_waker(Waker::noop()));
+        let Poll::Ready(error) = poll else {
+            return Poll::Pending;
+        };
+        self.errors = None;
+        Poll::Ready(match error {
+            Some(error) => Err(anyhow::anyhow!("{error}")),
+            None => Ok(self.pipeline.clone()),
+        })
+    }
+
+    fn is_pending(&self) -> bool {
+        self.errors.is_some()
+    }
+}
+
+// The pipeline of a pass outside of the registry and the source of its shader
+// A pipeline built from a new source replaces it once the device accepted it.
+pub(crate) struct PassPipeline {
+    pipeline: wgpu::RenderPipeline,
+    // kept to build the pipeline again when a snippet it includes changes
+    source: String,
+    pending: Option<(String, PendingPipeline)>,
+}
+
+impl PassPipeline {
+    pub(crate) fn new(pipeline: wgpu::RenderPipeline, source: &str) -> Self {
+        Self {
+            pipeline,
+            source: source.to_string(),
+            pending: None,
+        }
+    }
+
+    pub(crate) fn get(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    // a pipeline still pending is dropped
+    pub(crate) fn replace(&mut self, source: &str, pending: PendingPipeline) {
+        self.pending = Some((source.to_string(), pending));
+    }
+
+    // Swap in the pending pipeline if the device accepted it,
+    // None while nothing changed
+    pub(crate) fn poll(&mut self) -> Option<anyhow::Result<()>> {
+        let (source, pending) = self.pending.as_mut()?;
+        let Poll::Ready(result) = pending.poll() else {
+            return None;
+        };
+        let result = result.map(|pipeline| {
+            self.pipeline = pipeline;
+            self.source = std::mem::take(source);
+        });
+        self.pending = None;
+        Some(result)
+    }
+}
+
+// A pass drawing with its own pipeline, outside of a registry,
+// whose shader can be replaced while running (see Renderer::reload_shaders)
+pub(crate) trait ShaderPass {
+    // the name of its file in src/shaders
+    fn shader_file(&self) -> &'static str;
+
+    fn pipeline_mut(&mut self) -> &mut PassPipeline;
+
+    // Compile source and start building the pipeline with it
+    // the errors of the shader are returned, the ones of the pipeline
+    // come later from PassPipeline::poll
+    fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> anyhow::Result<()>;
+}
+
+// Preprocess and compile a shader, its errors are returned
+// instead of panicking in wgpu
+pub(crate) fn compile_shader(
+    device: &wgpu::Device,
+    preprocessor: &ShaderPreprocessor,
+    name: &str,
+    source: &str,
+    defines: &[&str],
+) -> anyhow::Result<(wgpu::ShaderModule, ShaderReflection)> {
+    let source = preprocessor
+        .process(source, defines)
+        .with_context(|| format!("Failed to preprocess {name}"))?;
+    // the errors of naga, before wgpu sees the shader
+    let reflection =
+        ShaderReflection::new(&source).with_context(|| format!("Failed to compile {name}"))?;
+    // what naga accepted but not the device makes the module invalid, and
+    // the pipelines built with it: their PendingPipeline reports the error
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    drop(device.pop_error_scope());
+    Ok((module, reflection))
+}
@@ -0,0 +1,171 @@
+// Shader composition: WGSL has no way to share code between files,
+// so the sources go through a few C-like directives before being compiled
+//
+// #include "name"   the snippet added with this name (once per shader)
+// #define NAME      NAME is defined for the rest of the source
+// #ifdef NAME       the lines up to #else or #endif are kept if NAME is defined
+// #ifndef NAME      the opposite
+// #else
+// #endif
+//
+// The directives must be alone on their line. Defines are only toggles,
+// they are not replaced in the source.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail, Context};
+
+// the snippets shared by the shaders of the renderer
+const BUILTIN_INCLUDES: [(&str, &str); 3] = [
+    ("camera.wgsl", include_str!("shaders/camera.wgsl")),
+    ("lights.wgsl", include_str!("shaders/lights.wgsl")),
+    ("instance.wgsl", include_str!("shaders/instance.wgsl")),
+];
+
+#[derive(Clone)]
+pub struct ShaderPreprocessor {
+    includes: HashMap<String, String>,
+}
+
+impl ShaderPreprocessor {
+    // With the snippets of the renderer: camera.wgsl, lights.wgsl and instance.wgsl
+    pub fn new() -> Self {
+        let includes = BUILTIN_INCLUDES
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect();
+        Self { includes }
+    }
+
+    // Add a snippet, or replace the one with the same name
+    pub fn add_include(&mut self, name: &str, source: &str) {
+        self.includes.insert(name.to_string(), source.to_string());
+    }
+
+    pub fn has_include(&self, name: &str) -> bool {
+        self.includes.contains_key(name)
+    }
+
+    // The WGSL source to compile, with the given names defined
+    pub fn process(&self, source: &str, defines: &[&str]) -> anyhow::Result<String> {
+        let mut state = State {
+            defines: defines.iter().map(|d| d.to_string()).collect(),
+            included: HashSet::new(),
+            output: String::new(),
+        };
+        self.process_into(source, &mut state)?;
+        Ok(state.output)
+    }
+
+    fn process_into(&self, source: &str, state: &mut State) -> anyhow::Result<()> {
+        // for each #ifdef not closed yet: whether its lines are kept,
+        // and whether an #else was seen
+        let mut conditions: Vec<Condition> = Vec::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let directive = line.trim();
+            let keeping = conditions.iter().all(|c| c.keep);
+            let result = if let Some(name) = directive.strip_prefix("#ifdef ") {
+                conditions.push(Condition::new(state.defines.contains(name.trim())));
+                Ok(())
+            } else if let Some(name) = directive.strip_prefix("#ifndef ") {
+                conditions.push(Condition::new(!state.defines.contains(name.trim())));
+                Ok(())
+            } else if directive == "#else" {
+                match conditions.last_mut() {
+                    Some(condition) if !condition.in_else => {
+                        condition.in_else = true;
+                        condition.keep = !condition.keep;
+                        Ok(())
+                    }
+                    Some(_) => Err(anyhow!("#else after #else")),
+                    None => Err(anyhow!("#else without #ifdef")),
+                }
+            } else if directive == "#endif" {
+                conditions
+                    .pop()
+                    .map(|_| ())
+                    .ok_or_else(|| anyhow!("#endif without #ifdef"))
+            } else if !keeping {
+                // lines of a removed block, directives included
+                Ok(())
+            } else if let Some(name) = directive.strip_prefix("#define ") {
+                state.defines.insert(name.trim().to_string());
+                Ok(())
+            } else if let Some(name) = directive.strip_prefix("#include ") {
+                self.include(name, state)
+            } else if directive.starts_with('#') {
+                Err(anyhow!("unknown directive {directive}"))
+            } else {
+                state.output.push_str(line);
+                state.output.push('\n');
+                Ok(())
+            };
+            result.with_context(|| format!("line {}", number + 1))?;
+        }
+
+        if !conditions.is_empty() {
+            bail!("#ifdef without #endif");
+        }
+        Ok(())
+    }
+
+    fn include(&self, name: &str, state: &mut State) -> anyhow::Result<()> {
+        let name = name
+            .trim()
+            .strip_prefix('"')
+            .and_then(|n| n.strip_suffix('"'))
+            .ok_or_else(|| anyhow!("expected #include \"name\""))?;
+        // a snippet included twice would declare everything twice
+        if !state.included.insert(name.to_string()) {
+            return Ok(());
+        }
+        let source = self
+            .includes
+            .get(name)
+            .ok_or_else(|| anyhow!("no include named {name}"))?;
+        self.process_into(source, state)
+            .with_context(|| format!("in {name}"))
+    }
+}
+
+impl Default for ShaderPreprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// what is shared by a shader and its includes
+struct State {
+    defines: HashSet<String>,
+    included: HashSet<String>,
+    output: String,
+}
+
+struct Condition {
+    keep: bool,
+    in_else: bool,
+}
+
+impl Condition {
+    fn new(keep: bool) -> Self {
+        Self {
+            keep,
+            in_else: false,
+        }
+    }
+}
+
+// The module of a shader of the renderer, which is known to compile
+pub(crate) fn create_builtin_module(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    let source = ShaderPreprocessor::new()
+        .process(source, &[])
+        .unwrap_or_else(|e| panic!("{label}: {e:#}"));
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
@@ -0,0 +1,234 @@
+// What a shader expects from the pipeline, read from its WGSL source with naga
+// (the compiler of wgpu): the bind group layouts are made from the @group/@binding
+// declarations, and the vertex buffers are checked against its @location inputs.
+use anyhow::{anyhow, bail, Context};
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    AddressSpace, Binding, ImageClass, ImageDimension, ScalarKind, ShaderStage, StorageAccess,
+    TypeInner,
+};
+
+pub struct ShaderReflection {
+    module: naga::Module,
+    info: naga::valid::ModuleInfo,
+}
+
+impl ShaderReflection {
+    // source must be preprocessed already, see ShaderPreprocessor
+    pub fn new(source: &str) -> anyhow::Result<Self> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|e| anyhow!("{}", e.emit_to_string(source)))?;
+        // needed to know which entry points use each binding
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|e| anyhow!("{}", e.emit_to_string(source)))?;
+        Ok(Self { module, info })
+    }
+
+    // The entries of a layout fitting the bindings of the group,
+    // visible to the stages using them
+    // Float textures are declared filterable, which the samplers
+    // of MyTexture need.
+    pub fn bind_group_layout_entries(
+        &self,
+        group: u32,
+    ) -> anyhow::Result<Vec<wgpu::BindGroupLayoutEntry>> {
+        let mut entries = Vec::new();
+        for (handle, variable) in self.module.global_variables.iter() {
+            let Some(binding) = &variable.binding else {
+                continue;
+            };
+            if binding.group != group {
+                continue;
+            }
+            let name = variable.name.as_deref().unwrap_or("unnamed");
+
+            let mut visibility = wgpu::ShaderStages::NONE;
+            for (i, entry_point) in self.module.entry_points.iter().enumerate() {
+                if !self.info.get_entry_point(i)[handle].is_empty() {
+                    visibility |= match entry_point.stage {
+                        ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                        ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                        ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                    };
+                }
+            }
+
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty: self.binding_type(variable).with_context(|| {
+                    format!("@group({group}) @binding({}) {name}", binding.binding)
+                })?,
+                count: None,
+            });
+        }
+        entries.sort_by_key(|entry| entry.binding);
+        Ok(entries)
+    }
+
+    fn binding_type(&self, variable: &naga::GlobalVariable) -> anyhow::Result<wgpu::BindingType> {
+        let buffer = |ty| wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        Ok(match variable.space {
+            AddressSpace::Uniform => buffer(wgpu::BufferBindingType::Uniform),
+            AddressSpace::Storage { access } => buffer(wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(StorageAccess::STORE),
+            }),
+            AddressSpace::Handle => match &self.module.types[variable.ty].inner {
+                TypeInner::Sampler { comparison: true } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+                }
+                TypeInner::Sampler { comparison: false } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+                }
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                } => {
+                    let (sample_type, multisampled) = match *class {
+                        ImageClass::Sampled { kind, multi } => {
+                            let sample_type = match kind {
+                                ScalarKind::Float => {
+                                    wgpu::TextureSampleType::Float { filterable: true }
+                                }
+                                ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                                ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                                kind => bail!("unsupported texture of {kind:?}"),
+                            };
+                            (sample_type, multi)
+                        }
+                        ImageClass::Depth { multi } => (wgpu::TextureSampleType::Depth, multi),
+                        ImageClass::Storage { .. } => bail!("storage textures are not supported"),
+                    };
+                    let view_dimension = match (dim, arrayed) {
+                        (ImageDimension::D1, false) => wgpu::TextureViewDimension::D1,
+                        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                        (ImageDimension::D3, false) => wgpu::TextureViewDimension::D3,
+                        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+                        (dim, arrayed) => bail!("unsupported texture {dim:?}, arrayed: {arrayed}"),
+                    };
+                    wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension,
+                        multisampled,
+                    }
+                }
+                inner => bail!("unsupported binding type {inner:?}"),
+            },
+            space => bail!("unsupported address space {space:?}"),
+        })
+    }
+
+    // Check that each @location input of the vertex entry point is in one
+    // of the vertex buffers, with a format of the same kind (float, int, uint)
+    pub fn check_vertex_layouts(
+        &self,
+        entry_point: &str,
+        layouts: &[wgpu::VertexBufferLayout],
+    ) -> anyhow::Result<()> {
+        let entry = self
+            .module
+            .entry_points
+            .iter()
+            .find(|e| e.name == entry_point && e.stage == ShaderStage::Vertex)
+            .ok_or_else(|| anyhow!("no vertex entry point named {entry_point}"))?;
+
+        // the arguments, and the members of the struct arguments
+        let mut inputs = Vec::new();
+        for argument in &entry.function.arguments {
+            let name = argument.name.as_deref().unwrap_or("unnamed");
+            match &self.module.types[argument.ty].inner {
+                TypeInner::Struct { members, .. } => {
+                    for member in members {
+                        let member_name = member.name.as_deref().unwrap_or("unnamed");
+                        inputs.push((format!("{name}.{member_name}"), &member.binding, member.ty));
+                    }
+                }
+                _ => inputs.push((name.to_string(), &argument.binding, argument.ty)),
+            }
+        }
+
+        let attributes = layouts
+            .iter()
+            .flat_map(|layout| layout.attributes)
+            .collect::<Vec<_>>();
+        for (i, attribute) in attributes.iter().enumerate() {
+            if attributes[..i]
+                .iter()
+                .any(|a| a.shader_location == attribute.shader_location)
+            {
+                bail!(
+                    "location {} is in several vertex attributes",
+                    attribute.shader_location
+                );
+            }
+        }
+
+        for (name, binding, ty) in inputs {
+            let Some(Binding::Location { location, .. }) = binding else {
+                continue;
+            };
+            let attribute = attributes
+                .iter()
+                .find(|a| a.shader_location == *location)
+                .ok_or_else(|| {
+                    anyhow!("{entry_point} reads {name} at location {location}, which is in no vertex buffer")
+                })?;
+            let kind = match &self.module.types[ty].inner {
+                TypeInner::Scalar(scalar) | TypeInner::Vector { scalar, .. } => scalar.kind,
+                inner => bail!("unsupported vertex input {name}: {inner:?}"),
+            };
+            if kind != format_kind(attribute.format) {
+                bail!(
+                    "{entry_point} reads {name} at location {location} as {kind:?}, but its format is {:?}",
+                    attribute.format
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+// Merge the entries of the layout of a group shared by several shaders:
+// the same bindings must have the same type, and are visible to
+// the stages of all of them
+pub fn merge_layout_entries(
+    entries: &[wgpu::BindGroupLayoutEntry],
+    others: &[wgpu::BindGroupLayoutEntry],
+) -> anyhow::Result<Vec<wgpu::BindGroupLayoutEntry>> {
+    let mut merged = entries.to_vec();
+    for other in others {
+        match merged.iter_mut().find(|e| e.binding == other.binding) {
+            Some(entry) if entry.ty == other.ty => entry.visibility |= other.visibility,
+            Some(entry) => bail!(
+                "binding {} is {:?} and {:?}",
+                other.binding,
+                entry.ty,
+                other.ty
+            ),
+            None => merged.push(*other),
+        }
+    }
+    merged.sort_by_key(|entry| entry.binding);
+    Ok(merged)
+}
+
+// what the shader gets from a vertex format
+fn format_kind(format: wgpu::VertexFormat) -> ScalarKind {
+    use wgpu::VertexFormat::*;
+    match format {
+        Uint8 | Uint8x2 | Uint8x4 | Uint16 | Uint16x2 | Uint16x4 | Uint32 | Uint32x2 | Uint32x3
+        | Uint32x4 => ScalarKind::Uint,
+        Sint8 | Sint8x2 | Sint8x4 | Sint16 | Sint16x2 | Sint16x4 | Sint32 | Sint32x2 | Sint32x3
+        | Sint32x4 => ScalarKind::Sint,
+        // normalized and floats
+        _ => ScalarKind::Float,
+    }
+}
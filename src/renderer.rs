@@ -0,0 +1,728 @@
+use std::iter;
+
+use winit::window::Window;
+
+use crate::{
+    capture,
+    depth_pass::DepthPass,
+    instance::*,
+    light::LightMarker,
+    mytexture::*,
+    pipeline::{PipelineDesc, PipelineRegistry, ShaderPass},
+    preprocessor::ShaderPreprocessor,
+    reflection::{merge_layout_entries, ShaderReflection},
+    scene::Scene,
+    shader_watcher::ShaderWatcher,
+    shadow::{ShadowPass, ShadowSettings},
+    skybox::SkyboxPass,
+    texture_cache::TextureCache,
+    vertex::*,
+    vertex_layout::{assert_distinct_locations, VertexLayout},
+};
+
+// name of the shader in the pipeline registry: the name
+// of its file, so the ShaderWatcher finds it
+const MESH_SHADER: &str = "shader_triangle_interpol_buffer";
+
+// the meshes are drawn with both
+const _: () = assert_distinct_locations(Vertex::ATTRIBUTES, InstanceRaw::ATTRIBUTES);
+
+// Where the frames end up: either presented to a window
+// or kept in an offscreen texture when there is no display
+enum RenderTarget<'a> {
+    Window {
+        surface: wgpu::Surface<'a>,
+        // The window must be declared after the surface so
+        // it gets dropped after it as the surface contains
+        // unsafe references to the window's resources.
+        window: &'a Window,
+    },
+    Offscreen {
+        texture: wgpu::Texture,
+    },
+}
+
+// Owns the GPU side: device, queue, the surface (or offscreen texture)
+// and the pipelines. It knows nothing about what is drawn,
+// a Scene is given to render() each frame.
+pub struct Renderer<'a> {
+    target: RenderTarget<'a>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    size: winit::dpi::PhysicalSize<u32>,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    textures: TextureCache,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    pipelines: PipelineRegistry,
+    // only in development mode, see watch_shaders
+    shader_watcher: Option<ShaderWatcher>,
+    use_color: bool,
+    depth_texture: MyTexture,
+    depth_pass: DepthPass,
+    show_depth: bool,
+    light_marker: LightMarker,
+    skybox_pass: SkyboxPass,
+    shadow_pass: ShadowPass,
+}
+
+impl<'a> Renderer<'a> {
+    pub async fn new(window: &'a Window) -> Self {
+        let size = window.inner_size();
+
+        // The instance is the first thing we instantiate in WGPU
+        // it'll handle the surface and the adapter
+
+        // The instance is a handle to our GPU
+        // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            #[cfg(not(target_arch = "wasm32"))]
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+
+        let surface = instance.create_surface(window).unwrap();
+
+        // adapter is a handle to the actual GPU
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .unwrap();
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: wgpu::Features::empty(),
+                    // WebGL doesn't support all of wgpu's features, so if
+                    // we're building for the web, we'll have to disable some.
+                    required_limits: if cfg!(target_arch = "wasm32") {
+                        wgpu::Limits::downlevel_webgl2_defaults()
+                    } else {
+                        wgpu::Limits::default()
+                    },
+                    label: None,
+                    memory_hints: Default::default(),
+                },
+                None, // Trace path
+            )
+            .await
+            .unwrap();
+
+        let surface_caps = surface.get_capabilities(&adapter);
+        //println!("{:?}", surface_caps);
+
+        // Shader code in this tutorial assumes an sRGB surface texture. Using a different
+        // one will result in all the colors coming out darker. If you want to support non
+        // sRGB surfaces, you'll need to account for that when drawing to the frame.
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: surface_caps.present_modes[0],
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        surface.configure(&device, &config);
+
+        Self::from_device(
+            RenderTarget::Window { surface, window },
+            device,
+            queue,
+            config,
+            size,
+        )
+    }
+
+    // Headless version of new: no window and no surface, frames are rendered
+    // into an offscreen texture with the same pipelines.
+    // It asks for the fallback adapter (a software rasterizer like llvmpipe)
+    // so it can run on machines without a GPU or a display.
+    pub async fn new_headless(width: u32, height: u32) -> Self {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+
+        // Software adapters are usually exposed through GL (llvmpipe)
+        // which is not part of the PRIMARY backends
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            })
+            .await
+            .expect("no fallback adapter found for headless rendering");
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: wgpu::Features::empty(),
+                    // software adapters don't always reach wgpu's default limits
+                    required_limits: wgpu::Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits()),
+                    label: None,
+                    memory_hints: Default::default(),
+                },
+                None, // Trace path
+            )
+            .await
+            .unwrap();
+
+        // Same assumption as for the surface: shaders expect an sRGB target
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        let texture = Self::create_offscreen_texture(&device, &config);
+
+        Self::from_device(
+            RenderTarget::Offscreen { texture },
+            device,
+            queue,
+            config,
+            size,
+        )
+    }
+
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            // COPY_SRC so the frame can be read back with capture()
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    // Everything after the device creation is shared between
+    // the windowed and the headless Renderer
+    fn from_device(
+        target: RenderTarget<'a>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        // The layouts are read from the shaders using them, see ShaderReflection
+        let preprocessor = ShaderPreprocessor::new();
+        let reflect = |label: &str, source: &str| {
+            preprocessor
+                .process(source, &[])
+                .and_then(|source| ShaderReflection::new(&source))
+                .unwrap_or_else(|e| panic!("{label}: {e:#}"))
+        };
+        let mesh_shader = reflect(
+            MESH_SHADER,
+            include_str!("shaders/shader_triangle_interpol_buffer.wgsl"),
+        );
+        let light_shader = reflect("light", include_str!("shaders/light.wgsl"));
+        let skybox_shader = reflect("skybox", include_str!("shaders/skybox.wgsl"));
+        let shadow_shader = reflect("shadow", include_str!("shaders/shadow.wgsl"));
+        let depth_shader = reflect("depth", include_str!("shaders/depth_visualization.wgsl"));
+        let entries = |shader: &ShaderReflection, group| {
+            shader
+                .bind_group_layout_entries(group)
+                .unwrap_or_else(|e| panic!("{e:#}"))
+        };
+
+        // a bind group describes a set of ressources and how they are accessed by a shader
+        // group 0 of the main pipeline, one bind group per material
+        let material_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &entries(&mesh_shader, 0),
+                label: Some("material_bind_group_layout"),
+            });
+
+        // the camera, the lights and the skybox, group 1 of the main pipeline
+        // and of the depth view, group 0 of the light markers and of the skybox
+        let camera_entries = [(&light_shader, 0), (&skybox_shader, 0), (&depth_shader, 1)]
+            .into_iter()
+            .try_fold(entries(&mesh_shader, 1), |merged, (shader, group)| {
+                merge_layout_entries(&merged, &entries(shader, group))
+            })
+            .unwrap_or_else(|e| panic!("camera bind group: {e:#}"));
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &camera_entries,
+                label: Some("camera_bind_group_layout"),
+            });
+
+        // created before the pipelines, as they read the shadow maps
+        // in group 2 of the main pipeline
+        let shadow_pass = ShadowPass::new(
+            &device,
+            ShadowSettings::default(),
+            &entries(&mesh_shader, 2),
+            &entries(&shadow_shader, 0),
+        );
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &material_bind_group_layout,
+                    &camera_bind_group_layout,
+                    shadow_pass.bind_group_layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+        // the pipelines are built when first drawn with, see mesh_pipeline
+        let mut pipelines = PipelineRegistry::new(&device, &render_pipeline_layout, config.format);
+        pipelines
+            .add_shader(
+                MESH_SHADER,
+                include_str!("shaders/shader_triangle_interpol_buffer.wgsl"),
+            )
+            .unwrap();
+
+        // created with the surface, and recreated with it in resize
+        let depth_texture = MyTexture::create_depth_texture(&device, &config, "depth_texture");
+        let depth_pass = DepthPass::new(
+            &device,
+            &depth_texture,
+            config.format,
+            &camera_bind_group_layout,
+        );
+
+        let light_marker = LightMarker::new(&device, config.format, &camera_bind_group_layout);
+        let skybox_pass = SkyboxPass::new(&device, config.format, &camera_bind_group_layout);
+
+        let textures = TextureCache::new(&device, &queue);
+
+        let renderer = Self {
+            target,
+            device,
+            queue,
+            config,
+            size,
+            material_bind_group_layout,
+            textures,
+            camera_bind_group_layout,
+            pipelines,
+            shader_watcher: None,
+            use_color: false,
+            depth_texture,
+            depth_pass,
+            show_depth: false,
+            light_marker,
+            skybox_pass,
+            shadow_pass,
+        };
+        // built now, so a shader not matching Vertex or InstanceRaw
+        // is reported at startup rather than at the first frame
+        renderer.pipelines.get(&renderer.mesh_pipeline());
+        renderer
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.config
+    }
+
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+
+    // layout of the bind group 0 (the textures and factors of a material)
+    // materials have to be created with it
+    pub fn material_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.material_bind_group_layout
+    }
+
+    // the image files loaded at runtime, see TextureCache::load
+    pub fn textures(&self) -> &TextureCache {
+        &self.textures
+    }
+
+    // layout of the bind group 1 (camera uniform, light list and skybox)
+    pub fn camera_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.camera_bind_group_layout
+    }
+
+    // the pipelines drawing the meshes, with the material, camera
+    // and shadow bind groups: more shaders can be added to it
+    pub fn pipelines(&self) -> &PipelineRegistry {
+        &self.pipelines
+    }
+
+    pub fn pipelines_mut(&mut self) -> &mut PipelineRegistry {
+        &mut self.pipelines
+    }
+
+    // Development mode: the shaders of the pipeline registry and of the other
+    // passes are compiled again from the .wgsl files of dir when they change,
+    // see reload_shaders
+    pub fn watch_shaders<P: AsRef<std::path::Path>>(&mut self, dir: P) {
+        self.shader_watcher = Some(ShaderWatcher::new(dir));
+    }
+
+    // Compile the watched shaders modified since the last call, see reload_shader_files
+    // Called every frame: the pipelines of the passes built by a previous
+    // reload are swapped in here, once the device accepted them.
+    pub fn reload_shaders(&mut self) {
+        self.poll_pass_pipelines();
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
+        let changed = watcher.changed();
+        if !changed.is_empty() {
+            self.reload_shader_files(&changed);
+        }
+    }
+
+    // Compile these .wgsl files again, for the shader or the snippet
+    // they replace (by file name)
+    // A shader with errors is reported in the log, and the pipelines keep
+    // using the last version which compiled.
+    pub fn reload_shader_files<P: AsRef<std::path::Path>>(&mut self, paths: &[P]) {
+        for path in paths {
+            let path = path.as_ref();
+            let (Some(file_name), Some(stem)) = (
+                path.file_name().and_then(|name| name.to_str()),
+                path.file_stem().and_then(|stem| stem.to_str()),
+            ) else {
+                continue;
+            };
+            let source = match std::fs::read_to_string(path) {
+                Ok(source) => source,
+                Err(e) => {
+                    log::error!("Failed to read {}: {e}", path.display());
+                    continue;
+                }
+            };
+            // the passes with their own pipeline, outside of the registry
+            // (the fields, as the device and the registry are used with them)
+            let mut passes: [&mut dyn ShaderPass; 4] = [
+                &mut self.light_marker,
+                &mut self.skybox_pass,
+                &mut self.shadow_pass,
+                &mut self.depth_pass,
+            ];
+            // snippets are included by file name, shaders named by their stem
+            let result = if self.pipelines.has_include(file_name) {
+                let result = self.pipelines.add_include(file_name, &source);
+                if result.is_ok() {
+                    // the passes may include it too, they are built again
+                    // from their current source
+                    for pass in &mut passes {
+                        let source = pass.pipeline_mut().source().to_string();
+                        if let Err(e) =
+                            pass.reload_shader(&self.device, self.pipelines.preprocessor(), &source)
+                        {
+                            log::error!("Failed to rebuild {}: {e:#}", pass.shader_file());
+                        }
+                    }
+                }
+                result
+            } else if self.pipelines.has_shader(stem) {
+                self.pipelines.add_shader(stem, &source)
+            } else if let Some(pass) = passes
+                .iter_mut()
+                .find(|pass| pass.shader_file() == file_name)
+            {
+                pass.reload_shader(&self.device, self.pipelines.preprocessor(), &source)
+            } else {
+                log::warn!("{} is not used by the renderer", path.display());
+                continue;
+            };
+            match result {
+                Ok(()) => log::info!("Reloaded {}", path.display()),
+                Err(e) => log::error!("Failed to reload {}: {e:#}", path.display()),
+            }
+        }
+        // the device usually knows right away
+        self.poll_pass_pipelines();
+    }
+
+    fn poll_pass_pipelines(&mut self) {
+        let passes: [&mut dyn ShaderPass; 4] = [
+            &mut self.light_marker,
+            &mut self.skybox_pass,
+            &mut self.shadow_pass,
+            &mut self.depth_pass,
+        ];
+        for pass in passes {
+            if let Some(Err(e)) = pass.pipeline_mut().poll() {
+                log::error!(
+                    "Keeping the previous {} pipeline: {e:#}",
+                    pass.shader_file()
+                );
+            }
+        }
+    }
+
+    // switch between the textured meshes and the vertex colored ones
+    // (the VERTEX_COLOR variant of the mesh shader)
+    pub fn use_color(&self) -> bool {
+        self.use_color
+    }
+
+    pub fn set_use_color(&mut self, use_color: bool) {
+        self.use_color = use_color;
+    }
+
+    // debug mode: show the depth buffer instead of the colors
+    pub fn show_depth(&self) -> bool {
+        self.show_depth
+    }
+
+    pub fn set_show_depth(&mut self, show_depth: bool) {
+        self.show_depth = show_depth;
+    }
+
+    pub fn shadow_settings(&self) -> &ShadowSettings {
+        self.shadow_pass.settings()
+    }
+
+    // resolution, bias and filtering of the shadows
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        self.shadow_pass.set_settings(&self.device, settings);
+    }
+
+    pub fn window(&self) -> &Window {
+        match &self.target {
+            RenderTarget::Window { window, .. } => window,
+            RenderTarget::Offscreen { .. } => panic!("a headless Renderer has no window"),
+        }
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.size = new_size;
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            match &mut self.target {
+                RenderTarget::Window { surface, .. } => {
+                    surface.configure(&self.device, &self.config)
+                }
+                RenderTarget::Offscreen { texture } => {
+                    *texture = Self::create_offscreen_texture(&self.device, &self.config)
+                }
+            }
+            self.depth_texture =
+                MyTexture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.depth_pass.resize(&self.device, &self.depth_texture);
+        }
+    }
+
+    pub fn render(&self, scene: &Scene) -> Result<(), wgpu::SurfaceError> {
+        // create a texture view with default settings
+        // we need this because we want to control how the render interacts with this
+        let (output, view) = match &self.target {
+            RenderTarget::Window { surface, .. } => {
+                // wait for the surface to provide a surface texture to write to
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), view)
+            }
+            // headless Renderers always draw into the same offscreen texture
+            RenderTarget::Offscreen { texture } => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+
+        self.draw(scene, &view);
+
+        if let Some(output) = output {
+            output.present();
+        }
+
+        Ok(())
+    }
+
+    // Render a frame and copy it back to the CPU
+    // in windowed mode the surface texture can't be read back,
+    // so the frame is drawn again in a temporary offscreen texture
+    pub fn capture(&self, scene: &Scene) -> image::RgbaImage {
+        let window_texture;
+        let texture = match &self.target {
+            RenderTarget::Offscreen { texture } => texture,
+            RenderTarget::Window { .. } => {
+                window_texture = Self::create_offscreen_texture(&self.device, &self.config);
+                &window_texture
+            }
+        };
+
+        self.draw(
+            scene,
+            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+
+        capture::read_texture(&self.device, &self.queue, texture)
+    }
+
+    // Screenshot helper, the format is deduced from the extension
+    pub fn save_png<P: AsRef<std::path::Path>>(
+        &self,
+        scene: &Scene,
+        path: P,
+    ) -> image::ImageResult<()> {
+        self.capture(scene).save(path)
+    }
+
+    fn draw(&self, scene: &Scene, view: &wgpu::TextureView) {
+        // Actual commands sent to the GPU
+        // Mots modern graphic frameworks need commands to be stored
+        // in a buffer before being sent to the GPU
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        // the shadow maps are read by the main pass, so they come first
+        self.shadow_pass.render(&self.queue, &mut encoder, scene);
+
+        // create a scope so we can call after encoder.finish()
+        // as begin_render_pass borrows encoder mutably
+        // we could also replace braces by drop(render_pass)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                // tells where we are drawing our colors to
+                // we only supply in the array the render target that we care about
+                color_attachments: &[
+                    // this is what @location(0) in the fragment shader targets
+                    Some(wgpu::RenderPassColorAttachment {
+                        // we use the texture view we created earlier to ensure we render to the screen
+                        view,
+                        // texture that will receive the resolved output
+                        // Same as view unless multisampling is enabled
+                        // we don't need this
+                        resolve_target: None,
+                        // tells the GPU what to do with the colors on the screen (the one specified by view)
+                        ops: wgpu::Operations {
+                            // load tells the GPU how to handle the colors stored from the previous frame
+                            load: wgpu::LoadOp::Clear(wgpu::Color {
+                                r: 0.1,
+                                g: 0.2,
+                                b: 0.3,
+                                a: 1.0,
+                            }),
+                            // we want to store our render results to the texture behind the texture view
+                            // (in our case the SurfaceTexture)
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    // cleared to the farthest depth each frame
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                // TODO: not in documentation but in source code
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.pipelines.get(&self.mesh_pipeline()));
+
+            render_pass.set_bind_group(2, self.shadow_pass.bind_group(), &[]);
+            Self::draw_meshes(&mut render_pass, scene);
+            self.light_marker.render(&mut render_pass, scene);
+            // last, so only the pixels left empty are shaded
+            self.skybox_pass.render(&mut render_pass, scene);
+        }
+
+        if self.show_depth {
+            self.depth_pass.render(&mut encoder, view, scene);
+        }
+
+        // finish the command buffer and send it
+        self.queue.submit(iter::once(encoder.finish()));
+    }
+
+    // The pipeline the meshes are drawn with
+    fn mesh_pipeline(&self) -> PipelineDesc {
+        let desc = PipelineDesc::new(MESH_SHADER)
+            .with_vertex_layouts(vec![Vertex::desc(), InstanceRaw::desc()]);
+        if self.use_color {
+            // the same shader, without the textures and the lights
+            desc.with_define("VERTEX_COLOR")
+        } else {
+            desc
+        }
+    }
+
+    // every mesh of the scene, once per instance of its object
+    fn draw_meshes(render_pass: &mut wgpu::RenderPass, scene: &Scene) {
+        render_pass.set_bind_group(1, scene.camera_bind_group(), &[]);
+
+        for object in scene.objects() {
+            // nothing to draw, and empty buffers can't be bound
+            if object.num_instances() == 0 {
+                continue;
+            }
+
+            // slice(..) means we use the entier buffer
+            render_pass.set_vertex_buffer(1, object.instance_buffer().slice(..));
+            for mesh in &object.model.meshes {
+                // empty buffers can't be bound either
+                if mesh.num_indices == 0 {
+                    continue;
+                }
+                let material = &object.model.materials[mesh.material];
+                render_pass.set_bind_group(0, &material.bind_group(), &[]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                // tells WebGPU to draw something with 3 vertices and 1 instance
+                // this is where in the shader @builtin(vertex_index) comes from
+                // render_pass.draw(0..3, 0..1);
+                // You can only have one index buffer set at a time
+                render_pass
+                    .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                // The draw method ignores the index buffer
+                render_pass.draw_indexed(0..mesh.num_indices, 0, 0..object.num_instances());
+            }
+        }
+    }
+}
@@ -0,0 +1,529 @@
+// Loading assets from disk into GPU meshes and materials
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use cgmath::prelude::*;
+
+use crate::{
+    camera::Camera,
+    instance::Instance,
+    material::Material,
+    model::{Mesh, Model},
+    mytexture::{MyTexture, SamplerOptions, TextureOptions},
+    renderer::Renderer,
+    scene::Scene,
+    texture_cache::TextureHandle,
+    vertex::Vertex,
+};
+
+// Load a Wavefront OBJ file and the MTL materials it references
+// Textures are looked up relative to the OBJ file.
+// Meshes without a material (or OBJ files without MTL) get a plain white one.
+pub fn load_obj<P: AsRef<Path>>(renderer: &Renderer, path: P) -> anyhow::Result<Model> {
+    let path = path.as_ref();
+    let device = renderer.device();
+    let queue = renderer.queue();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    // GPU_LOAD_OPTIONS: triangulate the faces, and use a single index
+    // for positions, texture coordinates and normals
+    let (obj_models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    // a missing MTL file is not fatal, the meshes will be white
+    let obj_materials = obj_materials.unwrap_or_else(|e| {
+        log::warn!("No materials for {}: {e}", path.display());
+        Vec::new()
+    });
+
+    let mut materials = Vec::new();
+    for m in obj_materials {
+        let mut material = match &m.diffuse_texture {
+            Some(file_name) => {
+                // OBJ texture coordinates often go outside of [0, 1] to tile the texture
+                let options = TextureOptions::default()
+                    .with_label(file_name)
+                    .with_sampler(
+                        SamplerOptions::default().with_address_mode(wgpu::AddressMode::Repeat),
+                    );
+                // materials of several files often share their textures
+                let texture = renderer
+                    .textures()
+                    .load(directory.join(file_name), &options)?;
+                Material::from_handle(
+                    device,
+                    renderer.material_bind_group_layout(),
+                    &m.name,
+                    texture,
+                )
+            }
+            None => Material::new(
+                device,
+                renderer.material_bind_group_layout(),
+                &m.name,
+                white_texture(device, queue),
+            ),
+        };
+        // Ns, the Phong exponent: the bigger, the smaller the highlights
+        // (the usual conversion to a GGX roughness)
+        if let Some(shininess) = m.shininess {
+            let roughness = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+            material = material.with_metallic_roughness(0.0, roughness);
+        }
+        // map_Bump, used for normal maps by most exporters
+        if let Some(file_name) = &m.normal_texture {
+            let options = TextureOptions::default()
+                .with_label(file_name)
+                .linear()
+                .with_sampler(
+                    SamplerOptions::default().with_address_mode(wgpu::AddressMode::Repeat),
+                );
+            let texture = renderer
+                .textures()
+                .load(directory.join(file_name), &options)?;
+            material = material.with_normal_map(texture);
+        }
+        materials.push(material);
+    }
+
+    let default_material = materials.len();
+    let mut needs_default_material = false;
+
+    let meshes = obj_models
+        .into_iter()
+        .filter(|m| {
+            // an object without faces has nothing to draw
+            let empty = m.mesh.positions.is_empty() || m.mesh.indices.is_empty();
+            if empty {
+                log::warn!("Skipping mesh {}, it has no triangles", m.name);
+            }
+            !empty
+        })
+        .map(|m| {
+            let positions = m
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect::<Vec<_>>();
+            let normals = if m.mesh.normals.is_empty() {
+                compute_normals(&positions, &m.mesh.indices)
+            } else {
+                m.mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(|n| [n[0], n[1], n[2]])
+                    .collect()
+            };
+            let vertices = (0..positions.len())
+                .map(|i| {
+                    let tex_coords = if m.mesh.texcoords.is_empty() {
+                        [0.0, 0.0]
+                    } else {
+                        // OBJ has the v axis pointing up, textures have it pointing down
+                        [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]]
+                    };
+                    Vertex::new(positions[i], tex_coords, normals[i])
+                })
+                .collect::<Vec<_>>();
+
+            let material = m.mesh.material_id.unwrap_or_else(|| {
+                needs_default_material = true;
+                default_material
+            });
+
+            Mesh::new(device, &m.name, &vertices, &m.mesh.indices, material)
+        })
+        .collect::<Vec<_>>();
+
+    if needs_default_material {
+        materials.push(Material::new(
+            device,
+            renderer.material_bind_group_layout(),
+            "default",
+            white_texture(device, queue),
+        ));
+    }
+
+    Ok(Model { meshes, materials })
+}
+
+// Load a glTF 2.0 file (.gltf with its buffers and images, or .glb) as a whole scene
+// Each glTF mesh becomes a Model, drawn once per node referencing it,
+// with the transforms of the node hierarchy flattened into its instances.
+// The first camera found in the hierarchy is used, otherwise the scene is seen
+// from a default point of view.
+pub fn load_gltf<P: AsRef<Path>>(renderer: &Renderer, path: P) -> anyhow::Result<Scene> {
+    let path = path.as_ref();
+    let device = renderer.device();
+    let queue = renderer.queue();
+
+    // import also reads the external buffers and decodes the images
+    let (document, buffers, images) = gltf::import(path)?;
+
+    // the textures made so far, shared by the materials using the same image
+    // with the same sampler and color space
+    let mut textures = HashMap::new();
+    let mut materials = Vec::new();
+    for m in document.materials() {
+        let name = m.name().unwrap_or("unnamed");
+        let pbr = m.pbr_metallic_roughness();
+        // the textures of the material, created from the images of the file
+        // colors are sRGB, the other textures are data
+        let mut load_texture = |texture: gltf::Texture, label: &str, linear: bool| {
+            let key = (texture.source().index(), texture.sampler().index(), linear);
+            if let Some(handle) = textures.get(&key) {
+                return anyhow::Ok(TextureHandle::clone(handle));
+            }
+            let mut options = TextureOptions::default()
+                .with_label(&format!("{name} {label}"))
+                .with_sampler(gltf_sampler(&texture.sampler()));
+            if linear {
+                options = options.linear();
+            }
+            let image = gltf_image(&images[texture.source().index()])?;
+            let texture = MyTexture::from_image_with(device, queue, &image, &options);
+            let handle = TextureHandle::new(texture);
+            textures.insert(key, handle.clone());
+            anyhow::Ok(handle)
+        };
+
+        let base_color_texture = match pbr.base_color_texture() {
+            Some(info) => load_texture(info.texture(), "base color", false)?,
+            None => TextureHandle::new(white_texture(device, queue)),
+        };
+        let mut material = Material::from_handle(
+            device,
+            renderer.material_bind_group_layout(),
+            name,
+            base_color_texture,
+        )
+        .with_base_color_factor(pbr.base_color_factor())
+        .with_metallic_roughness(pbr.metallic_factor(), pbr.roughness_factor())
+        .with_emissive(m.emissive_factor());
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            let texture = load_texture(info.texture(), "metallic roughness", true)?;
+            material = material.with_metallic_roughness_texture(texture);
+        }
+        if let Some(info) = m.normal_texture() {
+            let texture = load_texture(info.texture(), "normals", true)?;
+            material = material.with_normal_map(texture);
+            material.normal_scale = info.scale();
+        }
+        if let Some(info) = m.occlusion_texture() {
+            let texture = load_texture(info.texture(), "occlusion", true)?;
+            material = material.with_occlusion_texture(texture, info.strength());
+        }
+        if let Some(info) = m.emissive_texture() {
+            let texture = load_texture(info.texture(), "emissive", false)?;
+            material = material.with_emissive_texture(texture);
+        }
+        materials.push(material);
+    }
+    // for primitives without a material
+    let default_material = materials.len();
+    materials.push(Material::new(
+        device,
+        renderer.material_bind_group_layout(),
+        "default",
+        white_texture(device, queue),
+    ));
+
+    let mut models = Vec::new();
+    for mesh in document.meshes() {
+        let mesh_name = mesh.name().unwrap_or("unnamed");
+        let mut meshes = Vec::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipping {:?} primitive of mesh {mesh_name}, only triangles are supported",
+                    primitive.mode()
+                );
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = reader
+                .read_positions()
+                .ok_or_else(|| anyhow!("mesh {mesh_name} has no positions"))?
+                .collect::<Vec<_>>();
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if indices.is_empty() {
+                log::warn!("Skipping empty primitive of mesh {mesh_name}");
+                continue;
+            }
+            // glTF has the v axis pointing down like wgpu, no flip needed here
+            let tex_coords = match reader.read_tex_coords(0) {
+                Some(tex_coords) => tex_coords.into_f32().collect(),
+                None => vec![[0.0, 0.0]; positions.len()],
+            };
+            let normals = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => compute_normals(&positions, &indices),
+            };
+            // computed by Mesh::new when missing
+            let tangents = match reader.read_tangents() {
+                Some(tangents) => tangents.collect(),
+                None => vec![[0.0; 4]; positions.len()],
+            };
+            let vertices = (0..positions.len())
+                .map(|i| {
+                    Vertex::new(positions[i], tex_coords[i], normals[i]).with_tangent(tangents[i])
+                })
+                .collect::<Vec<_>>();
+
+            let material = primitive.material().index().unwrap_or(default_material);
+            meshes.push(Mesh::new(device, mesh_name, &vertices, &indices, material));
+        }
+        models.push(Model {
+            meshes,
+            // handles are cheap to clone, every model can have all the materials
+            materials: materials.clone(),
+        });
+    }
+
+    let gltf_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| anyhow!("{} has no scene", path.display()))?;
+
+    let mut instances = models.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+    let mut camera = None;
+    for node in gltf_scene.nodes() {
+        visit_node(
+            &node,
+            cgmath::Matrix4::identity(),
+            &mut instances,
+            &mut camera,
+        );
+    }
+
+    let config = renderer.config();
+    let aspect = config.width as f32 / config.height as f32;
+    let camera = camera
+        .and_then(|(gltf_camera, transform)| camera_from_gltf(&gltf_camera, transform, aspect))
+        .unwrap_or_else(|| Camera::look_at((0.0, 1.0, 5.0).into(), (0.0, 0.0, 0.0).into(), aspect));
+
+    let mut scene = Scene::new(renderer, camera);
+    for (model, instances) in models.into_iter().zip(instances) {
+        if !instances.is_empty() {
+            scene.add_model(device, model, instances);
+        }
+    }
+
+    Ok(scene)
+}
+
+// Load a cubemap from six image files, in the order
+// +X, -X, +Y, -Y, +Z, -Z (right, left, top, bottom, front, back)
+pub fn load_cubemap<P: AsRef<Path>>(
+    renderer: &Renderer,
+    faces: [P; 6],
+) -> anyhow::Result<MyTexture> {
+    let mut images = Vec::with_capacity(6);
+    for face in &faces {
+        let face = face.as_ref();
+        let bytes =
+            std::fs::read(face).with_context(|| format!("Failed to read {}", face.display()))?;
+        images.push(image::load_from_memory(&bytes)?);
+    }
+    let faces: [image::DynamicImage; 6] = images.try_into().unwrap();
+    let options = TextureOptions::default()
+        .with_label("cubemap")
+        .with_sampler(SamplerOptions::trilinear());
+    Ok(MyTexture::cube_from_faces(
+        renderer.device(),
+        renderer.queue(),
+        &faces,
+        &options,
+    ))
+}
+
+// Load a cubemap from a panorama, usually an HDR environment map (.hdr)
+// face_size is the width of the faces in texels, about a quarter
+// of the panorama width keeps its details.
+pub fn load_equirectangular<P: AsRef<Path>>(
+    renderer: &Renderer,
+    path: P,
+    face_size: u32,
+) -> anyhow::Result<MyTexture> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let panorama = image::load_from_memory(&bytes)?;
+    let options = TextureOptions::default()
+        .with_label(&path.display().to_string())
+        .with_sampler(SamplerOptions::trilinear());
+    Ok(MyTexture::cube_from_equirectangular(
+        renderer.device(),
+        renderer.queue(),
+        &panorama,
+        face_size,
+        &options,
+    ))
+}
+
+// Walk the node hierarchy, accumulating the transforms from the root
+fn visit_node<'a>(
+    node: &gltf::Node<'a>,
+    parent_transform: cgmath::Matrix4<f32>,
+    instances: &mut [Vec<Instance>],
+    camera: &mut Option<(gltf::Camera<'a>, cgmath::Matrix4<f32>)>,
+) {
+    let transform = parent_transform * cgmath::Matrix4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        instances[mesh.index()].push(instance_from_matrix(transform));
+    }
+    if camera.is_none() {
+        if let Some(c) = node.camera() {
+            *camera = Some((c, transform));
+        }
+    }
+
+    for child in node.children() {
+        visit_node(&child, transform, instances, camera);
+    }
+}
+
+// The position, rotation and scale of a node, without shear
+fn instance_from_matrix(transform: cgmath::Matrix4<f32>) -> Instance {
+    let position = transform.w.truncate();
+    let x = transform.x.truncate();
+    let y = transform.y.truncate();
+    let z = transform.z.truncate();
+    let mut scale = cgmath::Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+    // a node flattened on an axis has no direction there:
+    // it is rebuilt from the two others, or the rotation is dropped
+    let axis = |v: cgmath::Vector3<f32>, length: f32| (length > 1e-6).then(|| v / length);
+    let mut rotation = match (axis(x, scale.x), axis(y, scale.y), axis(z, scale.z)) {
+        (Some(x), Some(y), Some(z)) => cgmath::Matrix3::from_cols(x, y, z),
+        (None, Some(y), Some(z)) => cgmath::Matrix3::from_cols(y.cross(z), y, z),
+        (Some(x), None, Some(z)) => cgmath::Matrix3::from_cols(x, z.cross(x), z),
+        (Some(x), Some(y), None) => cgmath::Matrix3::from_cols(x, y, x.cross(y)),
+        _ => cgmath::Matrix3::identity(),
+    };
+    // a mirrored node (negative determinant) is a reflection, not a rotation:
+    // the reflection is moved to the scale of the x axis
+    if rotation.determinant() < 0.0 {
+        rotation.x = -rotation.x;
+        scale.x = -scale.x;
+    }
+    // an instance can't be sheared, the axes are made perpendicular again
+    let (x, y) = (rotation.x, rotation.y);
+    if x.dot(y).abs() > 1e-3 || x.dot(rotation.z).abs() > 1e-3 || y.dot(rotation.z).abs() > 1e-3 {
+        log::warn!("Ignoring the shear of a glTF node");
+    }
+    let y = (y - x * x.dot(y)).normalize();
+    let rotation = cgmath::Matrix3::from_cols(x, y, x.cross(y));
+
+    Instance::new(position)
+        .with_rotation(rotation.into())
+        .with_scale(scale)
+}
+
+// glTF cameras look down their local -Z axis, with +Y up
+// None for the orthographic ones, the default camera is used instead
+fn camera_from_gltf(
+    camera: &gltf::Camera,
+    transform: cgmath::Matrix4<f32>,
+    aspect: f32,
+) -> Option<Camera> {
+    let perspective = match camera.projection() {
+        gltf::camera::Projection::Perspective(p) => p,
+        gltf::camera::Projection::Orthographic(_) => {
+            log::warn!("Ignoring orthographic camera, only perspective cameras are supported");
+            return None;
+        }
+    };
+
+    let eye = cgmath::Point3::from_vec(transform.w.truncate());
+    let forward = transform.transform_vector(-cgmath::Vector3::unit_z());
+    let up = transform.transform_vector(cgmath::Vector3::unit_y());
+
+    // an infinite projection has no zfar, use the same as our default camera
+    Some(
+        Camera::look_at(eye, eye + forward, aspect)
+            .with_up(up.normalize())
+            .with_fovy(cgmath::Deg::from(cgmath::Rad(perspective.yfov())).0)
+            .with_clip_planes(perspective.znear(), perspective.zfar().unwrap_or(100.0)),
+    )
+}
+
+// glTF samplers repeat by default, and use the same filters as wgpu
+fn gltf_sampler(sampler: &gltf::texture::Sampler) -> SamplerOptions {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mut options = SamplerOptions::trilinear().with_address_modes(
+        address_mode(sampler.wrap_s()),
+        address_mode(sampler.wrap_t()),
+        wgpu::AddressMode::ClampToEdge,
+    );
+    if sampler.mag_filter() == Some(MagFilter::Nearest) {
+        options.mag_filter = wgpu::FilterMode::Nearest;
+    }
+    // the min filter also tells how to go from one mip level to the next
+    match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => {
+            options = options.with_filters(options.mag_filter, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::LinearMipmapNearest | MinFilter::Linear) => {
+            options = options.with_mipmap_filter(wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => options.min_filter = wgpu::FilterMode::Nearest,
+        Some(MinFilter::LinearMipmapLinear) | None => {}
+    }
+    options
+}
+
+// The image formats a base color texture can come in
+fn gltf_image(data: &gltf::image::Data) -> anyhow::Result<image::DynamicImage> {
+    use gltf::image::Format;
+
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let image = match data.format {
+        Format::R8 => image::GrayImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8 => image::GrayAlphaImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8B8 => image::RgbImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8B8A8 => image::RgbaImage::from_raw(width, height, pixels).map(Into::into),
+        format => bail!("unsupported image format {format:?}"),
+    };
+    image.ok_or_else(|| anyhow!("image data doesn't match its size"))
+}
+
+// Smooth normals for meshes that come without them:
+// each vertex gets the average of the normals of the triangles using it,
+// weighted by their area (the cross product length)
+fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![cgmath::Vector3::zero(); positions.len()];
+    // a last incomplete triangle isn't drawn either
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| cgmath::Vector3::from(positions[triangle[i] as usize]));
+        let normal = (b - a).cross(c - a);
+        for &i in triangle {
+            normals[i as usize] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|n| {
+            if n.is_zero() {
+                [0.0, 0.0, 1.0]
+            } else {
+                n.normalize().into()
+            }
+        })
+        .collect()
+}
+
+// 1x1 white texture, for untextured materials
+fn white_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> MyTexture {
+    let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+    MyTexture::from_image(device, queue, &image.into())
+}
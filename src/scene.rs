@@ -0,0 +1,345 @@
+use cgmath::prelude::*;
+
+// for create_buffer_init, use an extension trait
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::*,
+    instance::*,
+    instance_manager::InstanceManager,
+    light::{self, Light},
+    material::Material,
+    model::{Mesh, Model},
+    mytexture::*,
+    renderer::Renderer,
+    shadow,
+    vertex::*,
+};
+
+const NUM_INSTANCES_PER_ROW: u32 = 10;
+const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
+    NUM_INSTANCES_PER_ROW as f32 * 0.5,
+    0.0,
+    NUM_INSTANCES_PER_ROW as f32 * 0.5,
+);
+
+// A model drawn once per instance
+pub struct SceneObject {
+    pub model: Model,
+    instances: InstanceManager,
+}
+
+impl SceneObject {
+    fn new(device: &wgpu::Device, model: Model, instances: Vec<Instance>) -> Self {
+        Self {
+            model,
+            instances: InstanceManager::new(device, instances),
+        }
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        self.instances.instances()
+    }
+
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        self.instances.buffer()
+    }
+
+    pub fn num_instances(&self) -> u32 {
+        self.instances.len() as u32
+    }
+}
+
+// The light coming from everywhere when there is no skybox,
+// so the sides facing away from the lights are not black (linear, 0.1)
+const AMBIENT_COLOR: [u8; 4] = [26, 26, 26, 255];
+
+// lights the light buffer can hold before it has to grow
+const INITIAL_LIGHT_CAPACITY: usize = 16;
+
+// What is drawn: the models with their instances,
+// the lights and the camera looking at them.
+pub struct Scene {
+    camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    // kept to recreate the bind group when the light buffer grows
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    lights: Vec<Light>,
+    light_buffer: wgpu::Buffer,
+    light_capacity: usize,
+    skybox: Option<MyTexture>,
+    // bound instead of the skybox when there is none
+    ambient_cube: MyTexture,
+    objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    // An empty scene seen by camera, lit by the default light
+    // (remove it with remove_light if it's not wanted)
+    pub fn new(renderer: &Renderer, camera: Camera) -> Self {
+        let device = renderer.device();
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera.get_uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let light_buffer = Self::create_light_buffer(device, INITIAL_LIGHT_CAPACITY);
+        let ambient_cube = MyTexture::uniform_cube(device, renderer.queue(), AMBIENT_COLOR);
+        let camera_bind_group_layout = renderer.camera_bind_group_layout().clone();
+        let camera_bind_group = Self::create_camera_bind_group(
+            device,
+            &camera_bind_group_layout,
+            &camera_buffer,
+            &light_buffer,
+            &ambient_cube,
+        );
+
+        Self {
+            camera,
+            camera_buffer,
+            camera_bind_group_layout,
+            camera_bind_group,
+            lights: vec![Light::default()],
+            light_buffer,
+            light_capacity: INITIAL_LIGHT_CAPACITY,
+            skybox: None,
+            ambient_cube,
+            objects: Vec::new(),
+        }
+    }
+
+    // The textured pentagon drawn on a 10x10 grid of instances
+    pub fn demo(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let mut scene = Self::new(renderer, Camera::new(renderer.config()));
+
+        // the far rows of the grid are seen at a grazing angle
+        let options = TextureOptions::default()
+            .with_label("happy-tree")
+            .with_sampler(SamplerOptions::trilinear().with_anisotropy(16));
+        let texture = MyTexture::from_bytes(
+            device,
+            renderer.queue(),
+            include_bytes!("textures/happy-tree.png"),
+            &options,
+        )
+        .expect("the embedded texture is a valid PNG");
+        let material = Material::new(
+            device,
+            renderer.material_bind_group_layout(),
+            "happy-tree",
+            texture,
+        );
+        let pentagon = Model {
+            meshes: vec![Mesh::new(device, "pentagon", VERTICES, INDICES, 0)],
+            materials: vec![material],
+        };
+
+        let instances = (0..NUM_INSTANCES_PER_ROW)
+            .flat_map(|z| {
+                (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+                    let position = cgmath::Vector3 {
+                        x: x as f32,
+                        y: 0.0,
+                        z: z as f32,
+                    } - INSTANCE_DISPLACEMENT;
+
+                    let rotation = if position.is_zero() {
+                        // this is needed so an object at (0, 0, 0) won't get scaled to zero
+                        // as Quaternions can affect scale if they're not created correctly
+                        cgmath::Quaternion::from_axis_angle(
+                            cgmath::Vector3::unit_z(),
+                            cgmath::Deg(0.0),
+                        )
+                    } else {
+                        cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+                    };
+
+                    Instance::new(position).with_rotation(rotation)
+                })
+            })
+            .collect::<Vec<_>>();
+        scene.add_model(device, pentagon, instances);
+
+        scene
+    }
+
+    // Add a model drawn at each of the instances
+    // returns its index, to use with set_instances
+    pub fn add_model(
+        &mut self,
+        device: &wgpu::Device,
+        model: Model,
+        instances: Vec<Instance>,
+    ) -> usize {
+        self.objects
+            .push(SceneObject::new(device, model, instances));
+        self.objects.len() - 1
+    }
+
+    // Replace the instances of an object, its instance buffer grows to fit them
+    pub fn set_instances(
+        &mut self,
+        device: &wgpu::Device,
+        object: usize,
+        instances: Vec<Instance>,
+    ) {
+        self.objects[object].instances.set_all(device, instances);
+    }
+
+    // To add, remove or move the instances of an object,
+    // changes are sent to the GPU by update()
+    pub fn instances_mut(&mut self, object: usize) -> &mut InstanceManager {
+        &mut self.objects[object].instances
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    // returns the index to use with light_mut and remove_light
+    pub fn add_light(&mut self, device: &wgpu::Device, light: Light) -> usize {
+        self.lights.push(light);
+        if self.lights.len() > self.light_capacity {
+            // the bind group points to the old buffer, it has to be recreated too
+            self.light_capacity = (self.light_capacity * 2).max(self.lights.len());
+            self.light_buffer = Self::create_light_buffer(device, self.light_capacity);
+            self.recreate_camera_bind_group(device);
+        }
+        self.lights.len() - 1
+    }
+
+    // changes are sent to the GPU by update()
+    pub fn light_mut(&mut self, index: usize) -> &mut Light {
+        &mut self.lights[index]
+    }
+
+    // the lights after index move down by one
+    pub fn remove_light(&mut self, index: usize) -> Light {
+        self.lights.remove(index)
+    }
+
+    pub fn skybox(&self) -> Option<&MyTexture> {
+        self.skybox.as_ref()
+    }
+
+    // A cubemap (see MyTexture::cube_from_faces) drawn behind the objects,
+    // which also reflect it. None goes back to the clear color.
+    pub fn set_skybox(&mut self, device: &wgpu::Device, skybox: Option<MyTexture>) {
+        self.skybox = skybox;
+        self.recreate_camera_bind_group(device);
+    }
+
+    fn recreate_camera_bind_group(&mut self, device: &wgpu::Device) {
+        self.camera_bind_group = Self::create_camera_bind_group(
+            device,
+            &self.camera_bind_group_layout,
+            &self.camera_buffer,
+            &self.light_buffer,
+            self.skybox.as_ref().unwrap_or(&self.ambient_cube),
+        );
+    }
+
+    // Upload what changed since the last frame
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        // we have many options here, like
+        // * create a separte buffer and copy its content to the camera_buffer (staging buffer, usual way ?)
+        // * call mapping method map_read_async map_write_async
+        // * write_buffer as below (maybe also write_buffer_with ?)
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera.get_uniform()]),
+        );
+        // the light count and the last mip level of the skybox, then the lights
+        let count = self.lights.len() as u32;
+        let max_lod = self
+            .skybox
+            .as_ref()
+            .map_or(0, |skybox| skybox.texture.mip_level_count() - 1) as f32;
+        queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[count, max_lod.to_bits(), 0, 0]),
+        );
+        if !self.lights.is_empty() {
+            let mut shadow_layers = vec![None; self.lights.len()];
+            for (layer, light_index) in shadow::shadow_casters(&self.lights).enumerate() {
+                shadow_layers[light_index] = Some(layer as u32);
+            }
+            let lights = self
+                .lights
+                .iter()
+                .zip(shadow_layers)
+                .map(|(light, shadow_layer)| light.to_raw(shadow_layer))
+                .collect::<Vec<_>>();
+            queue.write_buffer(
+                &self.light_buffer,
+                light::LIGHT_LIST_HEADER_SIZE,
+                bytemuck::cast_slice(&lights),
+            );
+        }
+        for object in &mut self.objects {
+            object.instances.update(queue);
+        }
+    }
+
+    fn create_light_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Buffer"),
+            size: light::light_list_size(capacity),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_camera_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        light_buffer: &wgpu::Buffer,
+        skybox: &MyTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&skybox.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&skybox.sampler),
+                },
+            ],
+            label: Some("camera_bind_group"),
+        })
+    }
+
+    pub fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        &self.camera_bind_group
+    }
+
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+}
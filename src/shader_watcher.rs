@@ -0,0 +1,75 @@
+// Development mode: the WGSL files of a directory are checked for changes,
+// so shaders can be edited while the application runs
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// Looks at the modification times of the .wgsl files,
+// no more often than INTERVAL as it is asked every frame
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_check: Instant,
+}
+
+impl ShaderWatcher {
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    // The files already there are not reported until they change
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        let modified = Self::scan(&dir);
+        Self {
+            dir,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // The files created or modified since the last check,
+    // empty if it was less than INTERVAL ago
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_check.elapsed() < Self::INTERVAL {
+            return Vec::new();
+        }
+        self.check()
+    }
+
+    // Same as changed, looking at the files now
+    pub fn check(&mut self) -> Vec<PathBuf> {
+        self.last_check = Instant::now();
+
+        let modified = Self::scan(&self.dir);
+        let changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = modified;
+        changed
+    }
+
+    fn scan(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+        // a directory which can't be read has no shaders,
+        // it may be there later
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return HashMap::new();
+        };
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "wgsl" {
+                    return None;
+                }
+                let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                Some((path, modified))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,10 @@
+// same layout as CameraUniform
+struct CameraUniform {
+    // where the camera is, for the specular reflection
+    view_position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    // from clip space back to the world, for the skybox
+    inv_view_proj: mat4x4<f32>,
+    // x: znear, y: zfar, for the depth view
+    clip_planes: vec4<f32>,
+};
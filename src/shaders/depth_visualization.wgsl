@@ -0,0 +1,44 @@
+// Debug view of the depth buffer: near is black, far is white
+
+#include "camera.wgsl"
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// A single triangle covering the whole screen,
+// the parts outside of it are clipped
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(in_vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(in_vertex_index >> 1u) * 4 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+// bound as an unfilterable float texture, see DepthPass
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+
+// the camera of the scene, bound as in the main pipeline
+// its clip planes give back the distances from the depths
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // clip_position is in framebuffer coordinates in the fragment shader
+    // so it can be used to read the texel below the fragment
+    let depth = textureLoad(t_depth, vec2<i32>(in.clip_position.xy), 0).r;
+    // the depth buffer is not linear, most of the range is used close to the camera
+    let znear = camera.clip_planes.x;
+    let zfar = camera.clip_planes.y;
+    let linear_depth = znear * zfar / (zfar - depth * (zfar - znear));
+    let value = (linear_depth - znear) / (zfar - znear);
+    // stretch the first 10% of the range, where our scenes are
+    let shade = clamp(value * 10.0, 0.0, 1.0);
+    return vec4<f32>(vec3<f32>(shade), 1.0);
+}
@@ -0,0 +1,36 @@
+// same layout as InstanceRaw
+// parts of the dissambled matrix
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    // part of the texture shown by this instance: offset in xy, size in zw
+    @location(9) uv_rect: vec4<f32>,
+    // turns the normals, also when the instance is scaled
+    @location(10) normal_matrix_0: vec3<f32>,
+    @location(11) normal_matrix_1: vec3<f32>,
+    @location(12) normal_matrix_2: vec3<f32>,
+    // multiplies the base color
+    @location(13) tint: vec4<f32>,
+    // free for custom shaders, see Instance::custom
+    @location(14) custom: vec4<f32>,
+};
+
+// reassemble the matrix before using it
+fn instance_model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+}
+
+fn instance_normal_matrix(instance: InstanceInput) -> mat3x3<f32> {
+    return mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+}
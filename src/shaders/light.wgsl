@@ -0,0 +1,44 @@
+// The light markers: a small cube of the light color, drawn at each light position
+// one instance per light
+
+#include "camera.wgsl"
+#include "lights.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var<storage, read> light_list: LightList;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let light = light_list.lights[instance_index];
+    out.color = light.color;
+    if light.kind == LIGHT_DIRECTIONAL {
+        // nowhere to draw it: all the vertices at the same point
+        // give triangles without area, which are not rasterized
+        out.clip_position = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        return out;
+    }
+    // the cube is built around the origin, move it to the light
+    out.clip_position = camera.view_proj * vec4<f32>(model.position + light.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,28 @@
+// same layout as LightRaw
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    // cosines of the cone angles
+    inner_cutoff: f32,
+    // constant, linear, quadratic
+    attenuation: vec3<f32>,
+    outer_cutoff: f32,
+    // layer in t_shadow, -1 if the light casts no shadow
+    shadow_layer: i32,
+};
+
+// a runtime sized array has to be the last member
+// count is the number of lights in use, the buffer can hold more
+struct LightList {
+    count: u32,
+    // mip levels of the skybox - 1, see ambient_light
+    environment_max_lod: f32,
+    lights: array<Light>,
+};
+
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
@@ -1,27 +1,29 @@
+// The meshes, textured with their material
+// or colored by the position of their vertices when VERTEX_COLOR is defined
+
 // Vertex shader
 
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-};
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "instance.wgsl"
 
 // Because we've created a new bind group, we need to specify which one we're using in the shader. 
-// The number is determined by our render_pipeline_layout. The texture_bind_group_layout is listed 
+// The number is determined by our render_pipeline_layout. The material_bind_group_layout is listed 
 // first, thus it's group(0), and camera_bind_group is second, so it's group(1)
 
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// the lights are bound with the camera
+@group(1) @binding(1)
+var<storage, read> light_list: LightList;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>
-};
-
-// parts of the dissambled matrix
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    // w is the sign of the bitangent
+    @location(3) tangent: vec4<f32>,
 };
 
 struct VertexOutput {
@@ -31,20 +33,26 @@ struct VertexOutput {
     // i.e top-left corner at (0, 0)
     // so convenient for pixel coordinate in the buffer
     @builtin(position) clip_position: vec4<f32>,
+#ifdef VERTEX_COLOR
+    // but if we want to keep the position coordinates we have to pass them
+    // separately:
+    @location(0) vert_pos: vec3<f32>,
+    @location(1) tint: vec4<f32>,
+#else
     @location(0) tex_coords: vec2<f32>,
+    // lighting is computed in world space
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_tangent: vec4<f32>,
+    @location(4) tint: vec4<f32>,
+#endif
 };
 
 @vertex
 fn vs_main(
     model: VertexInput, instance: InstanceInput
 ) -> VertexOutput {
-     // reassemble the matrix before using it
-    let model_matrix = mat4x4<f32>(
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
-    );
+    let model_matrix = instance_model_matrix(instance);
 
     // var declared variables are mutable but must be explicetly typed
     var out: VertexOutput;
@@ -54,24 +62,321 @@ fn vs_main(
     // We do this because the camera_uniform.view_proj changes the coordinate system
     // from world space to camera space. 
     // Our model_matrix is a world space transformation, so we don't want to be in camera space when using it.
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
-    out.tex_coords = model.tex_coords;
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.tint = instance.tint;
+#ifdef VERTEX_COLOR
+    // keep the position coordinates
+    out.vert_pos = model.position;
+#else
+    out.tex_coords = instance.uv_rect.xy + model.tex_coords * instance.uv_rect.zw;
+    out.world_position = world_position.xyz;
+    // the tangent is along the surface, it follows the model matrix,
+    // but the normal has to stay perpendicular to it
+    let model_3x3 = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
+    );
+    out.world_normal = instance_normal_matrix(instance) * model.normal;
+    out.world_tangent = vec4<f32>(model_3x3 * model.tangent.xyz, model.tangent.w);
+#endif
 
     return out;
 }
 
 // Fragment shader
 
+#ifdef VERTEX_COLOR
+@fragment
+// @location(0) tells WebGPU to store the value
+// returned in the first color target
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.vert_pos, 1.0) * in.tint;
+}
+#else
+
+// same layout as MaterialUniform
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    // HAS_* bits: the optional textures actually there
+    textures: u32,
+};
+
+const HAS_NORMAL_MAP: u32 = 1u;
+const HAS_METALLIC_ROUGHNESS_TEXTURE: u32 = 2u;
+const HAS_OCCLUSION_TEXTURE: u32 = 4u;
+const HAS_EMISSIVE_TEXTURE: u32 = 8u;
+
 // uniforms
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var<uniform> material: Material;
 @group(0) @binding(1)
-var s_diffuse: sampler;
+var t_base_color: texture_2d<f32>;
+@group(0) @binding(2)
+var s_base_color: sampler;
+@group(0) @binding(3)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(4)
+var s_normal: sampler;
+@group(0) @binding(5)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(6)
+var s_metallic_roughness: sampler;
+@group(0) @binding(7)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(8)
+var s_occlusion: sampler;
+@group(0) @binding(9)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(10)
+var s_emissive: sampler;
+
+// the skybox, lighting the objects (dim grey without skybox)
+@group(1) @binding(2)
+var t_skybox: texture_cube<f32>;
+@group(1) @binding(3)
+var s_skybox: sampler;
+
+// shadow maps, one layer per shadow casting light
+@group(2) @binding(0)
+var t_shadow: texture_depth_2d_array;
+// comparison sampler: returns 1.0 where the fragment is lit, 0.0 where it's not
+@group(2) @binding(1)
+var s_shadow: sampler_comparison;
+
+const MAX_SHADOW_MAPS: u32 = 4u;
+
+// same layout as ShadowUniform
+struct ShadowUniform {
+    view_proj: array<mat4x4<f32>, MAX_SHADOW_MAPS>,
+    bias: f32,
+    pcf_radius: i32,
+    texel_size: f32,
+};
+
+@group(2) @binding(2)
+var<uniform> shadow: ShadowUniform;
+
+// How much of the light reaches world_position, from 0.0 to 1.0
+fn shadow_factor(layer: i32, world_position: vec3<f32>) -> f32 {
+    let light_position = shadow.view_proj[layer] * vec4<f32>(world_position, 1.0);
+    let ndc = light_position.xyz / light_position.w;
+    // outside of what the light saw: not shadowed
+    if ndc.z > 1.0 || abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0 {
+        return 1.0;
+    }
+    // from [-1, 1] with y up to texture coordinates [0, 1] with y down
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    let depth = ndc.z - shadow.bias;
+
+    // percentage closer filtering: average the comparisons around uv
+    // the Level variant has no uniform control flow requirement
+    var lit = 0.0;
+    for (var y = -shadow.pcf_radius; y <= shadow.pcf_radius; y += 1) {
+        for (var x = -shadow.pcf_radius; x <= shadow.pcf_radius; x += 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, layer, depth);
+        }
+    }
+    let samples = f32((2 * shadow.pcf_radius + 1) * (2 * shadow.pcf_radius + 1));
+    return lit / samples;
+}
+
+const PI: f32 = 3.14159265;
 
 @fragment
 // @location(0) tells WebGPU to store the value
 // returned in the first color target
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // sampled first: textureSample must be called in uniform control flow
+    // the missing textures are replaced by the base color one, see Material
+    let base_color_sample = textureSample(t_base_color, s_base_color, in.tex_coords);
+    let normal_sample = textureSample(t_normal, s_normal, in.tex_coords).xyz;
+    let metallic_roughness_sample = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+    let occlusion_sample = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+    let emissive_sample = textureSample(t_emissive, s_emissive, in.tex_coords).rgb;
+
+    let base_color = base_color_sample * material.base_color * in.tint;
+    var metallic = material.metallic;
+    // too smooth surfaces would give infinitely small highlights
+    var roughness = material.roughness;
+    if (material.textures & HAS_METALLIC_ROUGHNESS_TEXTURE) != 0u {
+        roughness *= metallic_roughness_sample.g;
+        metallic *= metallic_roughness_sample.b;
+    }
+    roughness = clamp(roughness, 0.04, 1.0);
+    var occlusion = 1.0;
+    if (material.textures & HAS_OCCLUSION_TEXTURE) != 0u {
+        occlusion = 1.0 + material.occlusion_strength * (occlusion_sample - 1.0);
+    }
+    var emissive = material.emissive;
+    if (material.textures & HAS_EMISSIVE_TEXTURE) != 0u {
+        emissive *= emissive_sample;
+    }
+
+    // interpolation between the vertices shortens the normals
+    var normal = normalize(in.world_normal);
+    if (material.textures & HAS_NORMAL_MAP) != 0u {
+        // the normal map is in tangent space: x along the tangent,
+        // y along the bitangent and z along the normal of the surface
+        let tangent = normalize(in.world_tangent.xyz - normal * dot(normal, in.world_tangent.xyz));
+        let bitangent = cross(normal, tangent) * in.world_tangent.w;
+        var tangent_normal = normal_sample * 2.0 - 1.0;
+        tangent_normal = vec3<f32>(tangent_normal.xy * material.normal_scale, tangent_normal.z);
+        normal = normalize(mat3x3<f32>(tangent, bitangent, normal) * tangent_normal);
+    }
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+
+    // the reflectance when looking straight at the surface:
+    // about 4% for dielectrics, the base color for metals
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+
+    var result = vec3<f32>(0.0);
+    for (var i = 0u; i < light_list.count; i += 1u) {
+        result += cook_torrance(
+            light_list.lights[i],
+            normal,
+            view_dir,
+            in.world_position,
+            base_color.rgb,
+            metallic,
+            roughness,
+            f0,
+        );
+    }
+    // the ambient light comes from the sky (a dim grey one without skybox),
+    // it isn't blocked by the shadows, only by the occlusion
+    let ambient = ambient_light(normal, view_dir, base_color.rgb, metallic, roughness, f0);
+    result = result + ambient * occlusion + emissive;
+
+    return vec4<f32>(result, base_color.a);
+}
+
+// Cook-Torrance: diffuse + specular from microfacets, for one light
+// The light intensity is scaled by pi, so a white light facing a white diffuse
+// surface lights it white (as the Blinn-Phong lighting did)
+fn cook_torrance(
+    light: Light,
+    normal: vec3<f32>,
+    view_dir: vec3<f32>,
+    world_position: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    f0: vec3<f32>,
+) -> vec3<f32> {
+    var light_dir: vec3<f32>;
+    var attenuation = 1.0;
+    if light.kind == LIGHT_DIRECTIONAL {
+        // direction is where the light goes, we need where it comes from
+        light_dir = -light.direction;
+    } else {
+        let to_light = light.position - world_position;
+        let distance = length(to_light);
+        light_dir = to_light / distance;
+        attenuation = 1.0 / (light.attenuation.x
+            + light.attenuation.y * distance
+            + light.attenuation.z * distance * distance);
+        if light.kind == LIGHT_SPOT {
+            // cosines get bigger when the angle gets smaller
+            let cos_angle = dot(-light_dir, light.direction);
+            attenuation *= smoothstep(light.outer_cutoff, light.inner_cutoff, cos_angle);
+        }
+    }
+    let radiance = light.color * light.intensity * attenuation;
+
+    var shadow = 1.0;
+    if light.shadow_layer >= 0 {
+        shadow = shadow_factor(light.shadow_layer, world_position);
+    }
+
+    // halfway between the light and the view directions
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    let n_dot_v = max(dot(normal, view_dir), 0.0);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+
+    // how many microfacets face half_dir, how many of them are not hidden
+    // by the others, and how much light they reflect
+    let d = distribution_ggx(n_dot_h, roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+    let f = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+    let specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+
+    // what is not reflected enters the surface, metals absorb all of it
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * albedo / PI;
+
+    return (diffuse + specular) * radiance * PI * n_dot_l * shadow;
+}
+
+// Trowbridge-Reitz GGX normal distribution
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+// Schlick-GGX for the view and the light directions
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+// Schlick's approximation of the Fresnel term: surfaces reflect
+// more light at grazing angles
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Image based lighting: the light coming from the whole skybox
+// Instead of convolving the cubemap beforehand, its mip levels are used:
+// the smallest ones are close to the average light around the normal,
+// and rough surfaces reflect blurrier (smaller) levels.
+fn ambient_light(
+    normal: vec3<f32>,
+    view_dir: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    f0: vec3<f32>,
+) -> vec3<f32> {
+    let max_lod = light_list.environment_max_lod;
+    let n_dot_v = max(dot(normal, view_dir), 0.0);
+
+    // the Level variant can be called outside of uniform control flow
+    let irradiance = textureSampleLevel(t_skybox, s_skybox, normal, max(max_lod - 1.0, 0.0)).rgb;
+    let reflected = textureSampleLevel(
+        t_skybox,
+        s_skybox,
+        reflect(-view_dir, normal),
+        roughness * max_lod,
+    ).rgb;
+
+    // Fresnel with the roughness: rough surfaces don't get as shiny at grazing angles
+    let f = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * albedo * irradiance;
+    let specular = reflected * environment_brdf(f0, n_dot_v, roughness);
+    return diffuse + specular;
 }
 
+// Analytic fit of the integral of the specular BRDF over the hemisphere
+// (the "split sum" lookup texture), from Karis' mobile shading
+fn environment_brdf(f0: vec3<f32>, n_dot_v: f32, roughness: f32) -> vec3<f32> {
+    let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    return f0 * ab.x + ab.y;
+}
+#endif
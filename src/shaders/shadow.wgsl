@@ -0,0 +1,19 @@
+// Shadow pass: the scene seen from a light, only the depth is kept
+
+#include "instance.wgsl"
+
+// view projection matrix of the light
+@group(0) @binding(0)
+var<uniform> light_view_proj: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput, instance: InstanceInput
+) -> @builtin(position) vec4<f32> {
+    let model_matrix = instance_model_matrix(instance);
+    return light_view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+}
@@ -0,0 +1,41 @@
+// The skybox: a triangle covering the screen, on the far plane,
+// showing the cubemap in the direction of each pixel
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(2)
+var t_skybox: texture_cube<f32>;
+@group(0) @binding(3)
+var s_skybox: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // the same as clip_position.xy, but interpolated in [-1, 1]
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // (-1, -1), (3, -1), (-1, 3): a triangle twice the size of the screen
+    // so the screen is inside it, without vertex buffer
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+    var out: VertexOutput;
+    // z = w: after the division by w the depth is 1.0,
+    // so it is behind everything drawn before
+    out.clip_position = vec4<f32>(x, y, 1.0, 1.0);
+    out.ndc = vec2<f32>(x, y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the point of the far plane seen through this pixel, back in the world
+    // only the direction from the camera matters: the sky is infinitely far
+    let far_point = camera.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = far_point.xyz / far_point.w - camera.view_position.xyz;
+    return textureSample(t_skybox, s_skybox, direction);
+}
@@ -0,0 +1,473 @@
+// Shadow mapping: the scene is first rendered from the point of view of
+// each shadow casting light, keeping only the depth. The main pass then
+// compares the distance of each fragment to the light with what the light "saw".
+use cgmath::prelude::*;
+
+use crate::{
+    camera::OPENGL_TO_WGPU_MATRIX,
+    instance::InstanceRaw,
+    light::{Light, LightKind},
+    mytexture::DEPTH_FORMAT,
+    pipeline::{compile_shader, PassPipeline, PendingPipeline, ShaderPass},
+    preprocessor::{create_builtin_module, ShaderPreprocessor},
+    scene::Scene,
+    vertex::Vertex,
+    vertex_layout::VertexLayout,
+};
+
+// layers of the shadow map texture array,
+// the shadow casting lights after this number don't cast shadows
+pub const MAX_SHADOW_MAPS: usize = 4;
+
+// a perspective projection can't see 180° or more, the shadows of
+// wider spots are cut at this angle
+const MAX_SPOT_FOVY: cgmath::Deg<f32> = cgmath::Deg(170.0);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowSettings {
+    // width and height of each shadow map,
+    // clamped to the texture sizes the device supports
+    pub resolution: u32,
+    // subtracted from the fragment depth before the comparison
+    // too small gives "shadow acne" (surfaces shadowing themselves)
+    // too big detaches the shadows from the objects ("peter panning")
+    pub bias: f32,
+    // percentage closer filtering: the shadow is the average of
+    // (2 * pcf_radius + 1)² comparisons around the fragment, 0 for hard shadows
+    pub pcf_radius: u32,
+    // directional lights have no position, their shadow map covers
+    // a box of this half size around the origin
+    pub directional_extent: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.002,
+            pcf_radius: 1,
+            directional_extent: 10.0,
+        }
+    }
+}
+
+// What the main pass needs to read the shadow maps
+// must match ShadowUniform in the shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    view_proj: [[[f32; 4]; 4]; MAX_SHADOW_MAPS],
+    bias: f32,
+    pcf_radius: i32,
+    // size of a shadow map texel in texture coordinates
+    texel_size: f32,
+    _padding: f32,
+}
+
+// The lights having a shadow map, in the order of the layers
+pub fn shadow_casters(lights: &[Light]) -> impl Iterator<Item = usize> + '_ {
+    lights
+        .iter()
+        .enumerate()
+        .filter(|(_, light)| light.cast_shadows && !matches!(light.kind, LightKind::Point { .. }))
+        .map(|(i, _)| i)
+        .take(MAX_SHADOW_MAPS)
+}
+
+// The view projection matrix of the "camera" rendering the shadow map of a light
+pub fn light_view_proj(light: &Light, settings: &ShadowSettings) -> Option<cgmath::Matrix4<f32>> {
+    // any up vector works, as long as it's not parallel to the direction
+    let up_for = |direction: cgmath::Vector3<f32>| {
+        if direction.normalize().y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        }
+    };
+
+    match light.kind {
+        // parallel rays: an orthographic projection,
+        // looking at the origin from far enough to see the whole box
+        LightKind::Directional { direction } => {
+            let extent = settings.directional_extent;
+            let eye = cgmath::Point3::origin() - direction.normalize() * extent * 2.0;
+            let view =
+                cgmath::Matrix4::look_at_rh(eye, cgmath::Point3::origin(), up_for(direction));
+            let proj = cgmath::ortho(-extent, extent, -extent, extent, 0.1, extent * 4.0);
+            Some(OPENGL_TO_WGPU_MATRIX * proj * view)
+        }
+        // a perspective projection covering the cone of the spot
+        // perspective depth is mostly spent close to the near plane, a near plane
+        // too close would make the bias very large compared to the scene
+        LightKind::Spot {
+            position,
+            direction,
+            outer_cutoff,
+            ..
+        } => {
+            let eye = cgmath::Point3::from_vec(position);
+            let view = cgmath::Matrix4::look_at_rh(eye, eye + direction, up_for(direction));
+            let fovy = if outer_cutoff * 2.0 < MAX_SPOT_FOVY {
+                outer_cutoff * 2.0
+            } else {
+                MAX_SPOT_FOVY
+            };
+            let proj = cgmath::perspective(fovy, 1.0, 0.5, 50.0);
+            Some(OPENGL_TO_WGPU_MATRIX * proj * view)
+        }
+        // would need 6 shadow maps (a cube map)
+        LightKind::Point { .. } => None,
+    }
+}
+
+// Renders the shadow maps, and gives the main pass a bind group to read them
+pub struct ShadowPass {
+    settings: ShadowSettings,
+    texture: wgpu::Texture,
+    // one view per layer, to render into
+    layer_views: Vec<wgpu::TextureView>,
+    // the view matrix of each layer, for the shadow pipeline
+    layer_buffers: Vec<wgpu::Buffer>,
+    layer_bind_groups: Vec<wgpu::BindGroup>,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: PassPipeline,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    // group 2 of the main pipeline
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ShadowPass {
+    // bind_group_entries: group 2 of the main pipeline, reading the shadow maps
+    // layer_entries: group 0 of shadow.wgsl, the view of one layer
+    // both read from the shaders, see ShaderReflection
+    pub fn new(
+        device: &wgpu::Device,
+        settings: ShadowSettings,
+        bind_group_entries: &[wgpu::BindGroupLayoutEntry],
+        layer_entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> Self {
+        let settings = ShadowSettings {
+            resolution: Self::supported_resolution(device, settings.resolution),
+            ..settings
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: bind_group_entries,
+            label: Some("shadow_bind_group_layout"),
+        });
+
+        let layer_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: layer_entries,
+                label: Some("shadow_layer_bind_group_layout"),
+            });
+
+        let layer_buffers = (0..MAX_SHADOW_MAPS)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Shadow Layer Buffer"),
+                    size: std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+        let layer_bind_groups = layer_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &layer_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("shadow_layer_bind_group"),
+                })
+            })
+            .collect();
+
+        let source = include_str!("shaders/shadow.wgsl");
+        let shader = create_builtin_module(device, "Shadow Shader", source);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&layer_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            size: std::mem::size_of::<ShadowUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            // linear filtering of comparisons gives a bit of free smoothing
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            // lit when the fragment is nearer (or as near) as what the light saw
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let (texture, layer_views, bind_group) = Self::create_shadow_maps(
+            device,
+            &bind_group_layout,
+            &sampler,
+            &uniform_buffer,
+            settings.resolution,
+        );
+
+        Self {
+            settings,
+            texture,
+            layer_views,
+            layer_buffers,
+            layer_bind_groups,
+            pipeline_layout,
+            pipeline: PassPipeline::new(pipeline, source),
+            uniform_buffer,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                // the same geometry as the main pass
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            // only the depth is written, no fragment shader needed
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                // single sided geometry (like the pentagon) still casts shadows
+                // when the light is behind it
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // surfaces seen at a grazing angle from the light need more bias
+                // than the constant one of the settings, the slope scale adds it
+                bias: wgpu::DepthBiasState {
+                    constant: 0,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    // the texture array, its layer views, and the bind group pointing to it
+    fn create_shadow_maps(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+        resolution: u32,
+    ) -> (wgpu::Texture, Vec<wgpu::TextureView>, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: MAX_SHADOW_MAPS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let layer_views = (0..MAX_SHADOW_MAPS as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Map Layer"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Maps View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&array_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("shadow_bind_group"),
+        });
+
+        (texture, layer_views, bind_group)
+    }
+
+    pub fn settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    // the shadow maps are recreated when the resolution changes
+    pub fn set_settings(&mut self, device: &wgpu::Device, mut settings: ShadowSettings) {
+        settings.resolution = Self::supported_resolution(device, settings.resolution);
+        if settings.resolution != self.texture.width() {
+            (self.texture, self.layer_views, self.bind_group) = Self::create_shadow_maps(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                &self.uniform_buffer,
+                settings.resolution,
+            );
+        }
+        self.settings = settings;
+    }
+
+    // The resolution clamped to the texture sizes of the device
+    fn supported_resolution(device: &wgpu::Device, resolution: u32) -> u32 {
+        let supported = resolution.clamp(1, device.limits().max_texture_dimension_2d);
+        if supported != resolution {
+            log::warn!("Shadow map resolution {resolution} is not supported, using {supported}");
+        }
+        supported
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // Render the shadow maps of the scene lights,
+    // before the main pass which reads them
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, scene: &Scene) {
+        let mut uniform = ShadowUniform {
+            view_proj: [cgmath::Matrix4::identity().into(); MAX_SHADOW_MAPS],
+            bias: self.settings.bias,
+            pcf_radius: self.settings.pcf_radius as i32,
+            texel_size: 1.0 / self.settings.resolution as f32,
+            _padding: 0.0,
+        };
+
+        for (layer, light_index) in shadow_casters(scene.lights()).enumerate() {
+            let Some(view_proj) = light_view_proj(&scene.lights()[light_index], &self.settings)
+            else {
+                continue;
+            };
+            uniform.view_proj[layer] = view_proj.into();
+            // written before the command buffer is submitted, so it's used by this frame
+            queue.write_buffer(
+                &self.layer_buffers[layer],
+                0,
+                bytemuck::cast_slice(&[uniform.view_proj[layer]]),
+            );
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[layer],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(self.pipeline.get());
+            render_pass.set_bind_group(0, &self.layer_bind_groups[layer], &[]);
+            for object in scene.objects() {
+                if object.num_instances() == 0 {
+                    continue;
+                }
+                render_pass.set_vertex_buffer(1, object.instance_buffer().slice(..));
+                for mesh in &object.model.meshes {
+                    if mesh.num_indices == 0 {
+                        continue;
+                    }
+                    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..mesh.num_indices, 0, 0..object.num_instances());
+                }
+            }
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+}
+
+impl ShaderPass for ShadowPass {
+    fn shader_file(&self) -> &'static str {
+        "shadow.wgsl"
+    }
+
+    fn pipeline_mut(&mut self) -> &mut PassPipeline {
+        &mut self.pipeline
+    }
+
+    fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        let (shader, _) = compile_shader(device, preprocessor, "Shadow Shader", source, &[])?;
+        let pending = PendingPipeline::new(device, || {
+            Self::create_pipeline(device, &self.pipeline_layout, &shader)
+        });
+        self.pipeline.replace(source, pending);
+        Ok(())
+    }
+}
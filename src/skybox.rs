@@ -0,0 +1,129 @@
+use crate::{
+    mytexture::DEPTH_FORMAT,
+    pipeline::{compile_shader, PassPipeline, PendingPipeline, ShaderPass},
+    preprocessor::{create_builtin_module, ShaderPreprocessor},
+    scene::Scene,
+};
+
+// Draws the skybox of the scene where no object was drawn,
+// so it has to come after the objects in the pass
+pub struct SkyboxPass {
+    format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: PassPipeline,
+}
+
+impl SkyboxPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let source = include_str!("shaders/skybox.wgsl");
+        let shader = create_builtin_module(device, "Skybox Shader", source);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            // the cubemap is bound with the camera
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, format, &shader);
+
+        Self {
+            format,
+            pipeline_layout,
+            pipeline: PassPipeline::new(pipeline, source),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                // the vertices are made from their index in the shader
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // on the far plane: LessEqual passes where the depth buffer
+            // still has its clear value, i.e. where nothing was drawn
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    // Must be called in a pass with the depth buffer of the scene
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, scene: &Scene) {
+        if scene.skybox().is_none() {
+            return;
+        }
+
+        render_pass.set_pipeline(self.pipeline.get());
+        render_pass.set_bind_group(0, scene.camera_bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl ShaderPass for SkyboxPass {
+    fn shader_file(&self) -> &'static str {
+        "skybox.wgsl"
+    }
+
+    fn pipeline_mut(&mut self) -> &mut PassPipeline {
+        &mut self.pipeline
+    }
+
+    fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        let (shader, _) = compile_shader(device, preprocessor, "Skybox Shader", source, &[])?;
+        let pending = PendingPipeline::new(device, || {
+            Self::create_pipeline(device, &self.pipeline_layout, self.format, &shader)
+        });
+        self.pipeline.replace(source, pending);
+        Ok(())
+    }
+}
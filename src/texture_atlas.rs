@@ -0,0 +1,130 @@
+// Many images packed in a single texture
+// Instances of one object pick their image with Instance::uv_rect,
+// so they can all look different and still be drawn in one draw call.
+use anyhow::bail;
+
+use crate::mytexture::{MyTexture, TextureOptions};
+
+// The part of a texture an instance shows: its texture coordinates
+// are scaled by size, then moved by offset
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvRect {
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl UvRect {
+    // the whole texture, texture coordinates are unchanged
+    pub const FULL: Self = Self {
+        offset: [0.0, 0.0],
+        size: [1.0, 1.0],
+    };
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+pub struct TextureAtlas {
+    texture: MyTexture,
+    rects: Vec<UvRect>,
+}
+
+impl TextureAtlas {
+    // texels copied around each image from its borders, so the linear filtering
+    // and the smaller mip levels don't blend in the neighbouring images
+    const PADDING: u32 = 4;
+    // a texel of the mip level n covers 2^n texels of the image:
+    // past PADDING, the levels would mix the images
+    const MIP_LEVELS: u32 = Self::PADDING.ilog2() + 1;
+
+    // The images are put in a grid of cells the size of the largest one,
+    // in the order given: rect(i) is where images[i] ended up.
+    // Texture coordinates outside of [0, 1] show the padding and the
+    // other images, the Repeat address mode can't be used to tile an image.
+    // The texture only has the mip levels the padding protects, see MIP_LEVELS.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::DynamicImage],
+        options: &TextureOptions,
+    ) -> anyhow::Result<Self> {
+        if images.is_empty() {
+            bail!("an atlas needs at least one image");
+        }
+        if let Some(i) = images
+            .iter()
+            .position(|i| i.width() == 0 || i.height() == 0)
+        {
+            bail!("image {i} of the atlas is empty");
+        }
+        let images = images.iter().map(|i| i.to_rgba8()).collect::<Vec<_>>();
+
+        let cell_width = images.iter().map(|i| i.width()).max().unwrap() + 2 * Self::PADDING;
+        let cell_height = images.iter().map(|i| i.height()).max().unwrap() + 2 * Self::PADDING;
+        // about as many columns as rows
+        let columns = (images.len() as f32).sqrt().ceil() as u32;
+        let rows = (images.len() as u32).div_ceil(columns);
+        let (width, height) = (columns * cell_width, rows * cell_height);
+
+        let mut atlas = image::RgbaImage::new(width, height);
+        let mut rects = Vec::with_capacity(images.len());
+        for (i, image) in images.iter().enumerate() {
+            let x0 = (i as u32 % columns) * cell_width;
+            let y0 = (i as u32 / columns) * cell_height;
+            let padding = Self::PADDING as i64;
+            for y in -padding..image.height() as i64 + padding {
+                for x in -padding..image.width() as i64 + padding {
+                    // the closest texel of the image, repeating its borders in the padding
+                    let pixel = image.get_pixel(
+                        x.clamp(0, image.width() as i64 - 1) as u32,
+                        y.clamp(0, image.height() as i64 - 1) as u32,
+                    );
+                    atlas.put_pixel(
+                        (x0 as i64 + padding + x) as u32,
+                        (y0 as i64 + padding + y) as u32,
+                        *pixel,
+                    );
+                }
+            }
+            rects.push(UvRect {
+                offset: [
+                    (x0 + Self::PADDING) as f32 / width as f32,
+                    (y0 + Self::PADDING) as f32 / height as f32,
+                ],
+                size: [
+                    image.width() as f32 / width as f32,
+                    image.height() as f32 / height as f32,
+                ],
+            });
+        }
+
+        let max_mip_levels = options.max_mip_levels.unwrap_or(u32::MAX);
+        let options = options
+            .clone()
+            .with_max_mip_levels(max_mip_levels.min(Self::MIP_LEVELS));
+        let texture = MyTexture::from_image_with(device, queue, &atlas.into(), &options);
+        Ok(Self { texture, rects })
+    }
+
+    // to create the Material of the objects using the atlas
+    pub fn texture(&self) -> &MyTexture {
+        &self.texture
+    }
+
+    // where the image i is, for Instance::with_uv_rect
+    pub fn rect(&self, i: usize) -> UvRect {
+        self.rects[i]
+    }
+
+    // number of images in the atlas
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+}
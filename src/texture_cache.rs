@@ -0,0 +1,187 @@
+// Textures loaded from disk at runtime, shared between the materials using them
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use anyhow::Context;
+
+use crate::mytexture::{MyTexture, TextureOptions};
+
+struct SharedTexture {
+    texture: MyTexture,
+    // incremented by replace, so the materials know their bind group is outdated
+    generation: u64,
+}
+
+// A shared texture: the clones all see the same GPU texture,
+// including after it has been replaced
+#[derive(Clone)]
+pub struct TextureHandle(Arc<RwLock<SharedTexture>>);
+
+impl TextureHandle {
+    pub fn new(texture: MyTexture) -> Self {
+        Self(Arc::new(RwLock::new(SharedTexture {
+            texture,
+            generation: 0,
+        })))
+    }
+
+    // cheap, the GPU resources are shared
+    pub fn texture(&self) -> MyTexture {
+        self.0.read().unwrap().texture.clone()
+    }
+
+    // Swap the texture of all the clones of this handle
+    // The materials rebuild their bind group with the same layout,
+    // so the pipelines using it don't change. Any texture made by MyTexture
+    // from an image (a filterable 2D texture and a filtering sampler) fits in it.
+    pub fn replace(&self, texture: MyTexture) {
+        let mut shared = self.0.write().unwrap();
+        shared.texture = texture;
+        shared.generation += 1;
+    }
+
+    // true when both handles share the same texture
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    // Identifies the current texture of the handle:
+    // it changes when the texture is replaced
+    pub fn version(&self) -> (usize, u64) {
+        (
+            Arc::as_ptr(&self.0) as usize,
+            self.0.read().unwrap().generation,
+        )
+    }
+}
+
+struct CacheEntry {
+    handle: TextureHandle,
+    // used again by reload
+    options: TextureOptions,
+}
+
+// Image files loaded once and shared, keyed by their path
+// The first load of a path decides its options,
+// the following ones get the same texture whatever their options.
+pub struct TextureCache {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    // behind a Mutex so loading only needs a shared reference to the Renderer
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+impl TextureCache {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The texture of the image file at path, read from disk the first time only
+    // Without a label in options, the texture is labelled with its path.
+    pub fn load<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &TextureOptions,
+    ) -> anyhow::Result<TextureHandle> {
+        let key = Self::key(path.as_ref());
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(&key) {
+            return Ok(entry.handle.clone());
+        }
+
+        let texture = self.read(&key, options)?;
+        let handle = TextureHandle::new(texture);
+        entries.insert(
+            key,
+            CacheEntry {
+                handle: handle.clone(),
+                options: options.clone(),
+            },
+        );
+        Ok(handle)
+    }
+
+    // The texture of path if it has been loaded
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<TextureHandle> {
+        let key = Self::key(path.as_ref());
+        let entries = self.entries.lock().unwrap();
+        entries.get(&key).map(|entry| entry.handle.clone())
+    }
+
+    // Show texture instead of the image at path, for all the handles of path
+    // A path which was not loaded yet gets texture as its first one.
+    pub fn replace<P: AsRef<Path>>(&self, path: P, texture: MyTexture) -> TextureHandle {
+        let key = Self::key(path.as_ref());
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(entry) => {
+                entry.handle.replace(texture);
+                entry.handle.clone()
+            }
+            None => {
+                let handle = TextureHandle::new(texture);
+                entries.insert(
+                    key,
+                    CacheEntry {
+                        handle: handle.clone(),
+                        options: TextureOptions::default(),
+                    },
+                );
+                handle
+            }
+        }
+    }
+
+    // Read the file of a loaded texture again, after it changed on disk
+    // On error, the previous texture is kept.
+    pub fn reload<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let key = Self::key(path.as_ref());
+        let entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get(&key)
+            .with_context(|| format!("{} is not a loaded texture", key.display()))?;
+        let texture = self.read(&key, &entry.options)?;
+        entry.handle.replace(texture);
+        Ok(())
+    }
+
+    // Forget the textures no material uses anymore
+    // (their only handle left is the one of the cache)
+    pub fn remove_unused(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| Arc::strong_count(&entry.handle.0) > 1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read(&self, path: &Path, options: &TextureOptions) -> anyhow::Result<MyTexture> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut options = options.clone();
+        if options.label.is_none() {
+            options.label = Some(path.display().to_string());
+        }
+        let texture = MyTexture::from_bytes(&self.device, &self.queue, &bytes, &options)
+            .with_context(|| format!("Failed to decode {}", path.display()))?;
+        Ok(texture)
+    }
+
+    // "textures/a.png" and "models/../textures/a.png" are the same texture
+    // paths that can't be resolved (files not there yet) are used as given
+    fn key(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+}
@@ -1,10 +1,24 @@
+// the trait and its derive macro
+use crate::VertexLayout;
+
 #[repr(C)]
 // Pod: plain old data, data can be accessed as &[u8]
 // Zeroable indactes we can use std::mem::zeroed
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+// VertexLayout: Vertex::desc(), the layout of the vertex buffers
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
 pub struct Vertex {
+    // @location(0) x: vec3<f32> in the vertex shader will match the position
+    #[location(0)]
     position: [f32; 3],
+    #[location(1)]
     tex_coords: [f32; 2],
+    // which way the surface faces, for lighting
+    #[location(2)]
+    normal: [f32; 3],
+    // direction of the u texture axis on the surface, for the normal maps
+    // w is 1.0 or -1.0: the sign of the bitangent (mirrored textures)
+    #[location(3)]
+    tangent: [f32; 4],
 }
 
 // Counter clock-wise (we are drawing only front-facing)
@@ -13,60 +27,144 @@ pub struct Vertex {
 // textures coordinates have y-axis pointing down
 // and wgpu world coordinates have y-axis pointing up
 // so important to do 1 - y instead of y for texture y
+// the pentagon is flat in the xy plane, all normals face +z
 pub const VERTICES: &[Vertex] = &[
     Vertex {
         position: [-0.0868241, 0.49240386, 0.0],
         tex_coords: [0.4131759, 0.00759614],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
     }, // A
     Vertex {
         position: [-0.49513406, 0.06958647, 0.0],
         tex_coords: [0.0048659444, 0.43041354],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
     }, // B
     Vertex {
         position: [-0.21918549, -0.44939706, 0.0],
         tex_coords: [0.28081453, 0.949397],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
     }, // C
     Vertex {
         position: [0.35966998, -0.3473291, 0.0],
         tex_coords: [0.85967, 0.84732914],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
     }, // D
     Vertex {
         position: [0.44147372, 0.2347359, 0.0],
         tex_coords: [0.9414737, 0.2652641],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
     }, // E
 ];
 
 // Pod and Zeroable arleady implemented for basic types by bytemuck
-pub const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+pub const INDICES: &[u32] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
 
 impl Vertex {
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            // how wide is Vertex
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            // tells the pipeline if the data is per vertex data or per instance
-            step_mode: wgpu::VertexStepMode::Vertex,
-            // here a macro exists
-            // attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3]
-            // but has to be tweaked as it returns a temporary
-            // and we need to return from a function
-            // anyway it may be nice to understand what attributes looks like
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    // @location(0) x: vec3<f32> in the vertex shader will match the position
-                    shader_location: 0,
-                    // tells the shader it is a vec3<f32>
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    // @location(0) x: vec3<f32> in the vertex shader will match the color
-                    shader_location: 1,
-                    // tells the shader it is a vec2<f32>
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+    // without tangent: Mesh::new computes them
+    pub const fn new(position: [f32; 3], tex_coords: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position,
+            tex_coords,
+            normal,
+            tangent: [0.0; 4],
+        }
+    }
+
+    pub const fn with_tangent(mut self, tangent: [f32; 4]) -> Self {
+        self.tangent = tangent;
+        self
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    pub fn tex_coords(&self) -> [f32; 2] {
+        self.tex_coords
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    pub fn tangent(&self) -> [f32; 4] {
+        self.tangent
+    }
+}
+
+// Tangents of vertices which have texture coordinates and normals,
+// from the triangles using them (indices, 3 per triangle)
+// Each triangle gives the directions in which u and v grow on it,
+// the vertices average them over the triangles they belong to.
+pub fn compute_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    use cgmath::prelude::*;
+    use cgmath::{Vector2, Vector3};
+
+    let mut tangents = vec![Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![Vector3::zero(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let position = |i: usize| Vector3::from(vertices[i].position);
+        let uv = |i: usize| Vector2::from(vertices[i].tex_coords);
+        let (edge1, edge2) = (position(b) - position(a), position(c) - position(a));
+        let (delta_uv1, delta_uv2) = (uv(b) - uv(a), uv(c) - uv(a));
+
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        // the texture is not stretched on this triangle, nothing to learn from it
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / determinant;
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+        // the textures have v pointing down, but the normal maps have
+        // their green channel pointing up: the bitangent is where v decreases
+        let bitangent = -(edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = Vector3::from(vertex.normal);
+        // Gram-Schmidt: remove the part along the normal, so the
+        // tangent lies in the surface
+        let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+        if tangent.magnitude2() < f32::EPSILON {
+            // no texture coordinates: any direction of the surface will do
+            let axis = if normal.x.abs() < 0.9 {
+                Vector3::unit_x()
+            } else {
+                Vector3::unit_y()
+            };
+            tangent = axis - normal * normal.dot(axis);
+        }
+        let tangent = tangent.normalize();
+        let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+}
+
+// The tangents of the vertices which have none (all zero),
+// the ones given with the model are kept
+pub fn fill_missing_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    if vertices.iter().all(|v| v.tangent != [0.0; 4]) {
+        return;
+    }
+    let mut computed = vertices.to_vec();
+    compute_tangents(&mut computed, indices);
+    for (vertex, computed) in vertices.iter_mut().zip(computed) {
+        if vertex.tangent == [0.0; 4] {
+            vertex.tangent = computed.tangent;
         }
     }
 }
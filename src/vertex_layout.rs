@@ -0,0 +1,86 @@
+// Vertex buffer layouts generated from the structs uploaded to the GPU,
+// with #[derive(VertexLayout)] instead of offsets computed by hand
+
+// The layout of a struct read by the vertex shader
+// Derive it, each field giving its @location:
+//
+// #[repr(C)]
+// #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+// struct Vertex {
+//     #[location(0)]
+//     position: [f32; 3],
+// }
+pub trait VertexLayout {
+    // per vertex or per instance data
+    const STEP_MODE: wgpu::VertexStepMode;
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static>
+    where
+        Self: Sized,
+    {
+        wgpu::VertexBufferLayout {
+            // how wide is the struct
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: Self::STEP_MODE,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+// The formats of the types a field can have,
+// one per location: matrices take a location per column
+pub trait VertexAttributeType {
+    const FORMATS: &'static [wgpu::VertexFormat];
+}
+
+macro_rules! vertex_attribute_type {
+    ($($ty:ty => [$($format:ident),*]),* $(,)?) => {
+        $(
+            impl VertexAttributeType for $ty {
+                const FORMATS: &'static [wgpu::VertexFormat] = &[$(wgpu::VertexFormat::$format),*];
+            }
+        )*
+    };
+}
+
+vertex_attribute_type! {
+    f32 => [Float32],
+    [f32; 2] => [Float32x2],
+    [f32; 3] => [Float32x3],
+    [f32; 4] => [Float32x4],
+    u32 => [Uint32],
+    [u32; 2] => [Uint32x2],
+    [u32; 3] => [Uint32x3],
+    [u32; 4] => [Uint32x4],
+    i32 => [Sint32],
+    [i32; 2] => [Sint32x2],
+    [i32; 3] => [Sint32x3],
+    [i32; 4] => [Sint32x4],
+    [[f32; 2]; 2] => [Float32x2, Float32x2],
+    [[f32; 3]; 3] => [Float32x3, Float32x3, Float32x3],
+    [[f32; 4]; 4] => [Float32x4, Float32x4, Float32x4, Float32x4],
+}
+
+// Fails to compile when used in a const and two attributes have the same
+// location, in the same layout (pass an empty b) or in two layouts used together
+pub const fn assert_distinct_locations(a: &[wgpu::VertexAttribute], b: &[wgpu::VertexAttribute]) {
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = i + 1;
+        while j < a.len() {
+            if a[i].shader_location == a[j].shader_location {
+                panic!("two vertex attributes have the same location");
+            }
+            j += 1;
+        }
+        let mut j = 0;
+        while j < b.len() {
+            if a[i].shader_location == b[j].shader_location {
+                panic!("the vertex and instance layouts have attributes at the same location");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,104 @@
+// Golden image helpers shared by the integration tests
+//
+// A rendered frame is compared to a reference PNG stored in tests/golden.
+// Run the tests with UPDATE_GOLDEN=1 to (re)write the references,
+// for example after an intentional change in a shader.
+// On failure, the actual frame and a diff image are written in
+// target/golden-diff so they can be inspected.
+
+use std::path::PathBuf;
+
+use learn_wgpu::{Renderer, Scene, State};
+
+pub const WIDTH: u32 = 256;
+pub const HEIGHT: u32 = 192;
+
+// Maximum difference allowed on each channel of a pixel,
+// software rasterizers don't always round exactly the same way
+pub const TOLERANCE: u8 = 3;
+
+pub fn headless_state() -> State<'static> {
+    pollster::block_on(State::new_headless(WIDTH, HEIGHT))
+}
+
+// A headless state drawing the scene returned by build_scene
+pub fn headless_state_with<F>(build_scene: F) -> State<'static>
+where
+    F: FnOnce(&Renderer) -> Scene,
+{
+    let renderer = pollster::block_on(Renderer::new_headless(WIDTH, HEIGHT));
+    let scene = build_scene(&renderer);
+    State::from_parts(renderer, scene)
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"))
+}
+
+fn diff_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden-diff")
+}
+
+// Render one frame of the state and compare it to tests/golden/<name>.png
+pub fn assert_golden(name: &str, state: &mut State) {
+    state.update();
+    let actual = state.capture();
+    let path = golden_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| {
+            panic!(
+                "cannot open {}: {e}, run with UPDATE_GOLDEN=1",
+                path.display()
+            )
+        })
+        .to_rgba8();
+
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{name}: size mismatch with the golden image"
+    );
+
+    // differing pixels are shown in red over a darkened copy of the reference
+    let mut diff = image::RgbaImage::new(actual.width(), actual.height());
+    let mut mismatches = 0;
+    for ((e, a), d) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let differs =
+            e.0.iter()
+                .zip(a.0.iter())
+                .any(|(e, a)| e.abs_diff(*a) > TOLERANCE);
+        *d = if differs {
+            mismatches += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            image::Rgba([e[0] / 4, e[1] / 4, e[2] / 4, 255])
+        };
+    }
+
+    if mismatches > 0 {
+        let dir = diff_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        actual.save(dir.join(format!("{name}-actual.png"))).unwrap();
+        diff.save(dir.join(format!("{name}-diff.png"))).unwrap();
+        panic!(
+            "{name}: {mismatches} pixels differ from {}, see {}",
+            path.display(),
+            dir.display()
+        );
+    }
+}
@@ -0,0 +1,698 @@
+// Golden image regression tests
+// each scene is rendered headlessly and compared to tests/golden/<name>.png
+mod common;
+
+use cgmath::prelude::*;
+use learn_wgpu::{
+    resources, Attenuation, Camera, Instance, Light, Material, Mesh, Model, MyTexture,
+    SamplerOptions, Scene, ShadowSettings, TextureAtlas, TextureHandle, TextureOptions, Vertex,
+};
+
+#[test]
+fn textured_pentagon() {
+    let mut state = common::headless_state();
+    // a single pentagon at the origin, facing the default camera
+    state.set_instances(0, vec![Instance::new(cgmath::Vector3::zero())]);
+
+    common::assert_golden("textured_pentagon", &mut state);
+}
+
+#[test]
+fn instance_grid() {
+    // the 10x10 grid built by State
+    let mut state = common::headless_state();
+
+    common::assert_golden("instance_grid", &mut state);
+}
+
+#[test]
+fn distant_grid() {
+    // the grid seen from far away and close to the ground,
+    // sampled from the smaller mip levels
+    let mut state = common::headless_state();
+    let camera = state.scene_mut().camera_mut();
+    camera.eye = (0.0, 1.0, 12.0).into();
+    camera.target = (0.0, 0.0, -5.0).into();
+
+    common::assert_golden("distant_grid", &mut state);
+}
+
+#[test]
+fn address_modes() {
+    // a square with texture coordinates going from -1 to 2,
+    // repeated on the left and mirrored on the right
+    const VERTICES: &[Vertex] = &[
+        Vertex::new([-0.5, -0.5, 0.0], [-1.0, 2.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.0], [2.0, 2.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.0], [2.0, -1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [-1.0, -1.0], [0.0, 0.0, 1.0]),
+    ];
+    const INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
+
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let camera = Camera::look_at(
+            (0.0, 0.0, 1.6).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+
+        for (x, address_mode) in [
+            (-0.55, wgpu::AddressMode::Repeat),
+            (0.55, wgpu::AddressMode::MirrorRepeat),
+        ] {
+            let options = TextureOptions::default()
+                .with_label("happy-tree")
+                .with_sampler(SamplerOptions::trilinear().with_address_mode(address_mode));
+            let texture = MyTexture::from_bytes(
+                device,
+                renderer.queue(),
+                include_bytes!("../src/textures/happy-tree.png"),
+                &options,
+            )
+            .unwrap();
+            let material = Material::new(
+                device,
+                renderer.material_bind_group_layout(),
+                "happy-tree",
+                texture,
+            );
+            let square = Model {
+                meshes: vec![Mesh::new(device, "square", VERTICES, INDICES, 0)],
+                materials: vec![material],
+            };
+            scene.add_model(device, square, vec![Instance::new((x, 0.0, 0.0).into())]);
+        }
+        scene
+    });
+
+    common::assert_golden("address_modes", &mut state);
+}
+
+#[test]
+fn depth_visualization() {
+    // the instance grid seen through the depth buffer
+    let mut state = common::headless_state();
+    state.renderer_mut().set_show_depth(true);
+
+    common::assert_golden("depth_visualization", &mut state);
+}
+
+#[test]
+fn depth_visualization_clip_planes() {
+    // the same grid with the near plane further: the depths are
+    // linearized with the clip planes of the camera
+    let mut state = common::headless_state();
+    let camera = Camera::new(state.renderer().config()).with_clip_planes(1.0, 20.0);
+    *state.scene_mut().camera_mut() = camera;
+    state.renderer_mut().set_show_depth(true);
+
+    common::assert_golden("depth_visualization_clip_planes", &mut state);
+}
+
+#[test]
+fn obj_cube() {
+    let mut state = common::headless_state_with(|renderer| {
+        let camera = Camera::look_at(
+            (1.5, 1.5, 2.5).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        let cube = resources::load_obj(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/cube.obj"),
+        )
+        .unwrap();
+        scene.add_model(
+            renderer.device(),
+            cube,
+            vec![Instance::new(cgmath::Vector3::zero())],
+        );
+        scene
+    });
+
+    common::assert_golden("obj_cube", &mut state);
+}
+
+#[test]
+fn colored_light() {
+    // the cube lit from the left by an orange light, with its marker visible
+    let mut state = common::headless_state_with(|renderer| {
+        let camera = Camera::look_at(
+            (1.5, 1.5, 2.5).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        let cube = resources::load_obj(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/cube.obj"),
+        )
+        .unwrap();
+        scene.add_model(
+            renderer.device(),
+            cube,
+            vec![Instance::new(cgmath::Vector3::zero())],
+        );
+        *scene.light_mut(0) =
+            Light::point((-0.9, 0.9, 0.9).into(), [1.0, 0.6, 0.2]).with_intensity(1.5);
+        scene
+    });
+
+    common::assert_golden("colored_light", &mut state);
+}
+
+#[test]
+fn light_types() {
+    // the glTF scene lit by a dim directional light, a spot light from above
+    // and a ring of colored point lights (more than the initial light buffer holds)
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let mut scene = resources::load_gltf(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/scene.gltf"),
+        )
+        .unwrap();
+        scene.remove_light(0);
+
+        scene.add_light(
+            device,
+            Light::directional((-1.0, -1.0, -0.5).into(), [0.3, 0.3, 0.4]),
+        );
+        scene.add_light(
+            device,
+            Light::spot(
+                (1.2, 3.0, 0.5).into(),
+                (0.0, -1.0, 0.0).into(),
+                cgmath::Deg(15.0),
+                cgmath::Deg(25.0),
+                [1.0, 1.0, 0.8],
+            )
+            .with_intensity(2.0),
+        );
+        for i in 0..24 {
+            let angle = cgmath::Deg(i as f32 * 15.0);
+            let color = match i % 3 {
+                0 => [1.0, 0.2, 0.2],
+                1 => [0.2, 1.0, 0.2],
+                _ => [0.2, 0.2, 1.0],
+            };
+            scene.add_light(
+                device,
+                Light::point((2.5 * angle.cos(), 0.3, 2.5 * angle.sin()).into(), color)
+                    .with_attenuation(Attenuation::new(1.0, 0.7, 1.8)),
+            );
+        }
+        scene
+    });
+
+    common::assert_golden("light_types", &mut state);
+}
+
+#[test]
+fn shadows() {
+    // the glTF scene with a spot light and a directional light casting shadows
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let mut scene = resources::load_gltf(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/scene.gltf"),
+        )
+        .unwrap();
+        scene.remove_light(0);
+
+        scene.add_light(
+            device,
+            Light::directional((1.0, -1.0, -0.6).into(), [0.6, 0.6, 0.7]).with_shadows(),
+        );
+        scene.add_light(
+            device,
+            Light::spot(
+                (-3.0, 2.0, 1.0).into(),
+                (3.0, -2.0, -1.0).into(),
+                cgmath::Deg(25.0),
+                cgmath::Deg(35.0),
+                [1.0, 0.9, 0.7],
+            )
+            .with_shadows(),
+        );
+        scene
+    });
+    state.renderer_mut().set_shadow_settings(ShadowSettings {
+        resolution: 512,
+        pcf_radius: 2,
+        ..Default::default()
+    });
+
+    common::assert_golden("shadows", &mut state);
+}
+
+#[test]
+fn gltf_scene() {
+    // two cubes under a rotated parent node on a red ground,
+    // seen from the camera stored in the file
+    let mut state = common::headless_state_with(|renderer| {
+        resources::load_gltf(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/scene.gltf"),
+        )
+        .unwrap()
+    });
+
+    common::assert_golden("gltf_scene", &mut state);
+}
+
+#[test]
+fn replaced_texture() {
+    // two cubes sharing the texture of the cache,
+    // which is replaced by a checkerboard after they are loaded
+    let mut state = common::headless_state_with(|renderer| {
+        let camera = Camera::look_at(
+            (0.0, 2.0, 3.5).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        for x in [-1.0, 1.0] {
+            let cube = resources::load_obj(
+                renderer,
+                concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/cube.obj"),
+            )
+            .unwrap();
+            scene.add_model(
+                renderer.device(),
+                cube,
+                vec![Instance::new((x, 0.0, 0.0).into())],
+            );
+        }
+        scene
+    });
+
+    let checkerboard = image::RgbaImage::from_fn(8, 8, |x, y| {
+        if (x + y) % 2 == 0 {
+            image::Rgba([240, 240, 240, 255])
+        } else {
+            image::Rgba([30, 60, 200, 255])
+        }
+    });
+    let renderer = state.renderer();
+    let texture = MyTexture::from_image_with(
+        renderer.device(),
+        renderer.queue(),
+        &checkerboard.into(),
+        &TextureOptions::default().with_label("checkerboard"),
+    );
+    renderer.textures().replace(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/happy-tree.png"),
+        texture,
+    );
+
+    common::assert_golden("replaced_texture", &mut state);
+}
+
+#[test]
+fn atlas_grid() {
+    // a 10x10 grid of squares drawn in one call,
+    // each showing its own image of an atlas: the tree in 100 hues
+    const VERTICES: &[Vertex] = &[
+        Vertex::new([-0.4, 0.0, 0.4], [0.0, 1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([0.4, 0.0, 0.4], [1.0, 1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([0.4, 0.0, -0.4], [1.0, 0.0], [0.0, 1.0, 0.0]),
+        Vertex::new([-0.4, 0.0, -0.4], [0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    const INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
+
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let camera = Camera::look_at(
+            (0.0, 7.0, 6.0).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+
+        let tree = image::load_from_memory(include_bytes!("../src/textures/happy-tree.png"))
+            .unwrap()
+            .resize(32, 32, image::imageops::FilterType::Triangle);
+        let images = (0..100)
+            .map(|i| image::imageops::huerotate(&tree, i * 360 / 100).into())
+            .collect::<Vec<_>>();
+        let atlas = TextureAtlas::new(
+            device,
+            renderer.queue(),
+            &images,
+            &TextureOptions::default(),
+        )
+        .unwrap();
+
+        let material = Material::new(
+            device,
+            renderer.material_bind_group_layout(),
+            "atlas",
+            atlas.texture().clone(),
+        );
+        let square = Model {
+            meshes: vec![Mesh::new(device, "square", VERTICES, INDICES, 0)],
+            materials: vec![material],
+        };
+        let instances = (0..100)
+            .map(|i| {
+                let position = ((i % 10) as f32 - 4.5, 0.0, (i / 10) as f32 - 4.5);
+                Instance::new(position.into()).with_uv_rect(atlas.rect(i))
+            })
+            .collect();
+        scene.add_model(device, square, instances);
+        scene
+    });
+
+    common::assert_golden("atlas_grid", &mut state);
+}
+
+#[test]
+fn skybox_faces() {
+    // a cubemap with one color per face, seen towards the +X +Y +Z corner:
+    // red on the left (+X), green on top (+Y), blue on the right (+Z)
+    let mut state = common::headless_state_with(|renderer| {
+        let camera = Camera::look_at(
+            (0.0, 0.0, 0.0).into(),
+            (1.0, 0.8, 1.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        )
+        .with_fovy(90.0);
+        let mut scene = Scene::new(renderer, camera);
+        scene.remove_light(0);
+
+        let colors = [
+            [200, 40, 40],
+            [40, 200, 200],
+            [40, 200, 40],
+            [200, 40, 200],
+            [40, 40, 200],
+            [200, 200, 40],
+        ];
+        let faces = colors.map(|[r, g, b]| {
+            // darker towards the bottom right, to see the orientation of the faces
+            image::RgbaImage::from_fn(16, 16, |x, y| {
+                let shade = 1.0 - (x + y) as f32 / 64.0;
+                image::Rgba([
+                    (r as f32 * shade) as u8,
+                    (g as f32 * shade) as u8,
+                    (b as f32 * shade) as u8,
+                    255,
+                ])
+            })
+            .into()
+        });
+        let cubemap = MyTexture::cube_from_faces(
+            renderer.device(),
+            renderer.queue(),
+            &faces,
+            &TextureOptions::default(),
+        );
+        scene.set_skybox(renderer.device(), Some(cubemap));
+        scene
+    });
+
+    common::assert_golden("skybox_faces", &mut state);
+}
+
+#[test]
+fn skybox_reflections() {
+    // the cube in front of an HDR sky, reflecting it on its sides
+    let mut state = common::headless_state_with(|renderer| {
+        let camera = Camera::look_at(
+            (1.8, 0.6, 2.4).into(),
+            (0.0, 0.3, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        let cube = resources::load_obj(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/cube.obj"),
+        )
+        .unwrap();
+        scene.add_model(
+            renderer.device(),
+            cube,
+            vec![Instance::new(cgmath::Vector3::zero())],
+        );
+        let sky = resources::load_equirectangular(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/sky.hdr"),
+            64,
+        )
+        .unwrap();
+        scene.set_skybox(renderer.device(), Some(sky));
+        scene
+    });
+
+    common::assert_golden("skybox_reflections", &mut state);
+}
+
+#[test]
+fn normal_map() {
+    // two grey squares lit from the top left, the right one with
+    // a normal map of 4x4 bumps, the left one flat
+    const VERTICES: &[Vertex] = &[
+        Vertex::new([-0.5, -0.5, 0.0], [0.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.0], [1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+    const INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
+
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let queue = renderer.queue();
+        let camera = Camera::look_at(
+            (0.0, 0.0, 1.6).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        *scene.light_mut(0) = Light::point((-1.0, 1.0, 0.6).into(), [1.0, 1.0, 1.0]);
+
+        let grey = image::RgbaImage::from_pixel(1, 1, image::Rgba([180, 180, 180, 255])).into();
+        // half spheres: the normal points away from the center of each cell,
+        // the green channel going up in the image
+        let bumps = image::RgbaImage::from_fn(64, 64, |x, y| {
+            let dx = (x % 16) as f32 / 7.5 - 1.0;
+            let dy = (y % 16) as f32 / 7.5 - 1.0;
+            let r2 = dx * dx + dy * dy;
+            let normal = if r2 < 0.8 {
+                [dx, -dy, (1.0 - r2).sqrt()]
+            } else {
+                [0.0, 0.0, 1.0]
+            };
+            let length = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+            let [r, g, b] = normal.map(|c| ((c / length * 0.5 + 0.5) * 255.0) as u8);
+            image::Rgba([r, g, b, 255])
+        });
+        let normal_map = MyTexture::from_image_with(
+            device,
+            queue,
+            &bumps.into(),
+            &TextureOptions::default().linear(),
+        );
+
+        for (x, normal_map) in [(-0.55, None), (0.55, Some(normal_map))] {
+            let mut material = Material::new(
+                device,
+                renderer.material_bind_group_layout(),
+                "grey",
+                MyTexture::from_image(device, queue, &grey),
+            );
+            if let Some(normal_map) = normal_map {
+                material = material.with_normal_map(TextureHandle::new(normal_map));
+            }
+            let square = Model {
+                meshes: vec![Mesh::new(device, "square", VERTICES, INDICES, 0)],
+                materials: vec![material],
+            };
+            scene.add_model(device, square, vec![Instance::new((x, 0.0, 0.0).into())]);
+        }
+        scene
+    });
+
+    common::assert_golden("normal_map", &mut state);
+}
+
+// a UV sphere of radius 0.4, texture coordinates going around it
+fn sphere() -> (Vec<Vertex>, Vec<u32>) {
+    const RINGS: u32 = 16;
+    const SEGMENTS: u32 = 32;
+    let mut vertices = Vec::new();
+    for ring in 0..=RINGS {
+        let v = ring as f32 / RINGS as f32;
+        let (sin_theta, cos_theta) = (v * std::f32::consts::PI).sin_cos();
+        for segment in 0..=SEGMENTS {
+            let u = segment as f32 / SEGMENTS as f32;
+            let (sin_phi, cos_phi) = (u * std::f32::consts::TAU).sin_cos();
+            let normal = [sin_theta * cos_phi, cos_theta, -sin_theta * sin_phi];
+            vertices.push(Vertex::new(normal.map(|c| c * 0.4), [u, v], normal));
+        }
+    }
+    let mut indices = Vec::new();
+    for ring in 0..RINGS {
+        for segment in 0..SEGMENTS {
+            let a = ring * (SEGMENTS + 1) + segment;
+            let b = a + SEGMENTS + 1;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+    (vertices, indices)
+}
+
+#[test]
+fn pbr_materials() {
+    // spheres in front of the HDR sky: dielectric on the top row, metal on
+    // the bottom one, rougher from left to right
+    // and a glowing one, with an occlusion texture darkening its stripes
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let queue = renderer.queue();
+        let camera = Camera::look_at(
+            (0.0, 0.0, 4.6).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        *scene.light_mut(0) = Light::directional((-1.0, -1.0, -1.0).into(), [1.0, 1.0, 1.0]);
+        let sky = resources::load_equirectangular(
+            renderer,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/sky.hdr"),
+            64,
+        )
+        .unwrap();
+        scene.set_skybox(device, Some(sky));
+
+        let (vertices, indices) = sphere();
+        let gold = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 200, 90, 255])).into();
+        let mut add_sphere = |material: Material, x: f32, y: f32| {
+            let model = Model {
+                meshes: vec![Mesh::new(device, "sphere", &vertices, &indices, 0)],
+                materials: vec![material],
+            };
+            scene.add_model(device, model, vec![Instance::new((x, y, 0.0).into())]);
+        };
+
+        for (y, metallic) in [(0.5, 0.0), (-0.5, 1.0)] {
+            for (i, roughness) in [0.15, 0.4, 0.7, 1.0].into_iter().enumerate() {
+                let material = Material::new(
+                    device,
+                    renderer.material_bind_group_layout(),
+                    "gold",
+                    MyTexture::from_image(device, queue, &gold),
+                )
+                .with_metallic_roughness(metallic, roughness);
+                add_sphere(material, i as f32 * 0.95 - 2.0, y);
+            }
+        }
+
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])).into();
+        let stripes = image::RgbaImage::from_fn(64, 4, |x, _| {
+            let occlusion = if x % 4 < 2 { 255 } else { 0 };
+            image::Rgba([occlusion, occlusion, occlusion, 255])
+        });
+        let occlusion = MyTexture::from_image_with(
+            device,
+            queue,
+            &stripes.into(),
+            &TextureOptions::default().linear(),
+        );
+        let glowing = Material::new(
+            device,
+            renderer.material_bind_group_layout(),
+            "glowing",
+            MyTexture::from_image(device, queue, &white),
+        )
+        .with_metallic_roughness(0.0, 0.6)
+        .with_occlusion_texture(TextureHandle::new(occlusion), 1.0)
+        .with_emissive([0.2, 0.05, 0.3]);
+        add_sphere(glowing, 1.95, 0.0);
+        scene
+    });
+
+    common::assert_golden("pbr_materials", &mut state);
+}
+
+#[test]
+fn vertex_colors() {
+    // the textured shader built without its textures and lights:
+    // the meshes are colored by the position of their vertices
+    let mut state = common::headless_state();
+    state.renderer_mut().set_use_color(true);
+
+    common::assert_golden("vertex_colors", &mut state);
+}
+
+#[test]
+fn dynamic_instances() {
+    // the grid changed after being built: its first pentagon is removed,
+    // the others turn, and its buffer grows to take a row of raised pentagons
+    let mut state = common::headless_state();
+    let camera = state.scene_mut().camera_mut();
+    camera.eye = (0.0, 4.0, 7.0).into();
+    camera.target = (0.0, 0.0, -1.0).into();
+    let device = state.renderer().device().clone();
+    let instances = state.scene_mut().instances_mut(0);
+    instances.remove(0);
+    let rotation = cgmath::Quaternion::from_angle_y(cgmath::Deg(60.0));
+    for instance in instances.iter_mut() {
+        instance.rotation = instance.rotation * rotation;
+    }
+    for x in -2..=2 {
+        instances.add(&device, Instance::new((x as f32 * 1.2, 2.5, 1.0).into()));
+    }
+
+    common::assert_golden("dynamic_instances", &mut state);
+}
+
+#[test]
+fn scaled_instances() {
+    // one white sphere drawn squashed, stretched and turned,
+    // each instance with its own tint
+    // the highlights stay where the surface faces the light
+    let mut state = common::headless_state_with(|renderer| {
+        let device = renderer.device();
+        let camera = Camera::look_at(
+            (0.0, 0.0, 3.2).into(),
+            (0.0, 0.0, 0.0).into(),
+            common::WIDTH as f32 / common::HEIGHT as f32,
+        );
+        let mut scene = Scene::new(renderer, camera);
+        *scene.light_mut(0) = Light::directional((-1.0, -1.0, -1.0).into(), [1.0, 1.0, 1.0]);
+
+        let (vertices, indices) = sphere();
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])).into();
+        let material = Material::new(
+            device,
+            renderer.material_bind_group_layout(),
+            "white",
+            MyTexture::from_image(device, renderer.queue(), &white),
+        )
+        .with_metallic_roughness(0.0, 0.3);
+        let model = Model {
+            meshes: vec![Mesh::new(device, "sphere", &vertices, &indices, 0)],
+            materials: vec![material],
+        };
+        let instances = vec![
+            Instance::new((-1.2, 0.0, 0.0).into())
+                .with_scale((1.0, 0.4, 1.0).into())
+                .with_tint([1.0, 0.3, 0.3, 1.0]),
+            Instance::new((0.0, 0.0, 0.0).into())
+                .with_scale((0.6, 1.8, 0.6).into())
+                .with_tint([0.3, 1.0, 0.3, 1.0]),
+            Instance::new((1.2, 0.0, 0.0).into())
+                .with_rotation(cgmath::Quaternion::from_angle_z(cgmath::Deg(45.0)))
+                .with_scale((1.6, 0.6, 0.6).into())
+                .with_tint([0.3, 0.3, 1.0, 1.0]),
+        ];
+        scene.add_model(device, model, instances);
+        scene
+    });
+
+    common::assert_golden("scaled_instances", &mut state);
+}
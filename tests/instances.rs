@@ -0,0 +1,92 @@
+// Instances changed at runtime: buffer growth and the ranges written by update
+use std::ops::Range;
+
+use learn_wgpu::{Instance, InstanceManager, Renderer};
+
+fn instance(x: f32) -> Instance {
+    Instance::new((x, 0.0, 0.0).into())
+}
+
+fn manager(renderer: &Renderer, count: usize) -> InstanceManager {
+    let instances = (0..count).map(|x| instance(x as f32)).collect();
+    InstanceManager::new(renderer.device(), instances)
+}
+
+#[test]
+fn buffer_grows_when_full() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let mut instances = manager(&renderer, 4);
+    let buffer = instances.buffer().clone();
+
+    instances.add(renderer.device(), instance(4.0));
+
+    assert_eq!(instances.len(), 5);
+    assert_eq!(instances.capacity(), 8);
+    assert_ne!(instances.buffer(), &buffer);
+    // the new buffer is empty, everything is written again
+    assert_eq!(instances.dirty_ranges().len(), 1);
+    assert_eq!(instances.dirty_ranges()[0], 0..5);
+
+    // room is left for the next ones
+    let buffer = instances.buffer().clone();
+    instances.add(renderer.device(), instance(5.0));
+    assert_eq!(instances.buffer(), &buffer);
+    assert_eq!(instances.capacity(), 8);
+}
+
+#[test]
+fn only_changes_are_written() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let mut instances = manager(&renderer, 10);
+    assert!(instances.dirty_ranges().is_empty());
+
+    instances.get_mut(7).position.y = 1.0;
+    instances.set(2, instance(-2.0));
+    instances.get_mut(3).position.y = 1.0;
+    let expected: &[Range<usize>] = &[2..4, 7..8];
+    assert_eq!(instances.dirty_ranges(), expected);
+
+    // the instances after the removed one move down
+    instances.remove(5);
+    assert_eq!(instances.dirty_ranges(), &[2..4, 5..9]);
+
+    instances.update(renderer.queue());
+    assert!(instances.dirty_ranges().is_empty());
+}
+
+#[test]
+fn normals_stay_perpendicular_when_scaled() {
+    use cgmath::prelude::*;
+
+    let instance = instance(0.0)
+        .with_rotation(cgmath::Quaternion::from_angle_z(cgmath::Deg(30.0)))
+        .with_scale((3.0, 0.5, 1.0).into());
+    let model = cgmath::Matrix3::from_cols(
+        instance.model_matrix().x.truncate(),
+        instance.model_matrix().y.truncate(),
+        instance.model_matrix().z.truncate(),
+    );
+    // a slanted surface: its normal and a direction along it
+    let normal = cgmath::Vector3::new(1.0, 1.0, 0.0);
+    let along = cgmath::Vector3::new(1.0, -1.0, 0.0);
+
+    let world_normal = instance.normal_matrix() * normal;
+    let world_along = model * along;
+    assert!(world_normal.dot(world_along).abs() < 1e-5);
+    // the model matrix alone would bend the normal
+    assert!((model * normal).dot(world_along).abs() > 0.1);
+}
+
+#[test]
+fn flattened_instances_have_finite_normals() {
+    use cgmath::prelude::*;
+
+    // a quad flattened along y, like a shadow decal
+    let instance = instance(0.0).with_scale((2.0, 0.0, -1.0).into());
+    let world_normal = instance.normal_matrix() * cgmath::Vector3::new(1.0, 1.0, 1.0);
+
+    assert!(world_normal.x.is_finite() && world_normal.y.is_finite() && world_normal.z.is_finite());
+    // all the surfaces of a flat instance face along the flattened axis
+    let world_normal = world_normal.normalize();
+    assert!((world_normal.y - 1.0).abs() < 1e-5);
+}
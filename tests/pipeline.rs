@@ -0,0 +1,155 @@
+// Pipelines built on demand by the registry of the renderer,
+// and rebuilt when their shader changes
+use learn_wgpu::{PipelineDesc, Renderer, ShaderWatcher, State};
+
+// a triangle made from the vertex index, the bind groups are not read
+const SHADER: &str = "
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(f32(i) - 1.0, f32(i & 1u), 0.5, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+#[test]
+fn pipelines_are_built_once() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    renderer.pipelines_mut().add_shader("flat", SHADER).unwrap();
+    let pipelines = renderer.pipelines();
+    let before = pipelines.len();
+
+    let desc = PipelineDesc::new("flat");
+    pipelines.get(&desc);
+    pipelines.get(&desc);
+    assert_eq!(pipelines.len(), before + 1);
+
+    // any difference in the description is another pipeline
+    pipelines.get(&desc.clone().with_cull_mode(None));
+    pipelines.get(
+        &desc
+            .clone()
+            .with_topology(wgpu::PrimitiveTopology::LineList),
+    );
+    assert_eq!(pipelines.len(), before + 3);
+}
+
+#[test]
+fn replacing_a_shader_rebuilds_its_pipelines() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    renderer.pipelines_mut().add_shader("flat", SHADER).unwrap();
+    let desc = PipelineDesc::new("flat");
+    let before = renderer.pipelines().get(&desc);
+
+    renderer.pipelines_mut().add_shader("flat", SHADER).unwrap();
+
+    assert_ne!(renderer.pipelines().get(&desc), before);
+}
+
+#[test]
+fn invalid_shader_keeps_the_last_pipeline() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    renderer.pipelines_mut().add_shader("flat", SHADER).unwrap();
+    let desc = PipelineDesc::new("flat");
+    let before = renderer.pipelines().get(&desc);
+
+    let result = renderer
+        .pipelines_mut()
+        .add_shader("flat", &SHADER.replace("vec4<f32>(1.0)", "undefined"));
+
+    assert!(result.is_err());
+    assert_eq!(renderer.pipelines().get(&desc), before);
+}
+
+#[test]
+fn pipeline_errors_keep_the_last_pipeline() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    renderer.pipelines_mut().add_shader("flat", SHADER).unwrap();
+    let desc = PipelineDesc::new("flat");
+    let before = renderer.pipelines().get(&desc);
+
+    // compiles, but reads a vertex buffer the pipeline doesn't have
+    let with_input = SHADER.replace(
+        "@builtin(vertex_index) i: u32",
+        "@builtin(vertex_index) i: u32, @location(0) offset: f32",
+    );
+    renderer
+        .pipelines_mut()
+        .add_shader("flat", &with_input)
+        .unwrap();
+
+    assert_eq!(renderer.pipelines().get(&desc), before);
+}
+
+#[test]
+fn include_errors_keep_every_shader() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let pipelines = renderer.pipelines_mut();
+    pipelines
+        .add_include("values.wgsl", "const A: f32 = 1.0;\nconst B: f32 = 1.0;")
+        .unwrap();
+    let with_value = |value: &str| {
+        let color = SHADER.replace("vec4<f32>(1.0)", &format!("vec4<f32>({value})"));
+        format!("#include \"values.wgsl\"\n{color}")
+    };
+    pipelines.add_shader("with_a", &with_value("A")).unwrap();
+    pipelines.add_shader("with_b", &with_value("B")).unwrap();
+    let descs = [PipelineDesc::new("with_a"), PipelineDesc::new("with_b")];
+    let before = descs.each_ref().map(|desc| renderer.pipelines().get(desc));
+
+    // with_a still compiles, but with_b doesn't: neither of them changes
+    let result = renderer
+        .pipelines_mut()
+        .add_include("values.wgsl", "const A: f32 = 0.5;");
+
+    assert!(result.is_err());
+    assert_eq!(
+        descs.each_ref().map(|desc| renderer.pipelines().get(desc)),
+        before
+    );
+}
+
+#[test]
+fn watcher_reports_changed_files() {
+    let dir = std::env::temp_dir().join(format!("shader_watcher_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("old.wgsl"), SHADER).unwrap();
+    let mut watcher = ShaderWatcher::new(&dir);
+
+    std::fs::write(dir.join("new.wgsl"), SHADER).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a shader").unwrap();
+    let changed = watcher.check();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(changed, vec![dir.join("new.wgsl")]);
+}
+
+#[test]
+fn pass_shaders_are_reloaded() {
+    let dir = std::env::temp_dir().join(format!("pass_shaders_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("depth_visualization.wgsl");
+    let mut state = pollster::block_on(State::new_headless(16, 16));
+    state.renderer_mut().set_show_depth(true);
+
+    // the depth view, drawn in red instead of the depth
+    let depth_shader = include_str!("../src/shaders/depth_visualization.wgsl").replace(
+        "return vec4<f32>(vec3<f32>(shade), 1.0);",
+        "return vec4<f32>(1.0, 0.0, 0.0, 1.0);",
+    );
+    std::fs::write(&path, &depth_shader).unwrap();
+    state.renderer_mut().reload_shader_files(&[&path]);
+    let red = *state.capture().get_pixel(8, 8);
+
+    // an error keeps the last pipeline
+    std::fs::write(&path, "undefined").unwrap();
+    state.renderer_mut().reload_shader_files(&[&path]);
+    let still_red = *state.capture().get_pixel(8, 8);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(red, image::Rgba([255, 0, 0, 255]));
+    assert_eq!(still_red, red);
+}
@@ -0,0 +1,74 @@
+// Shader composition with #include, #define and #ifdef
+use learn_wgpu::ShaderPreprocessor;
+
+fn lines(source: &str) -> Vec<&str> {
+    source.lines().collect()
+}
+
+#[test]
+fn includes_are_inserted_once() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor.add_include("a.wgsl", "const A: u32 = 1u;");
+    preprocessor.add_include("b.wgsl", "#include \"a.wgsl\"\nconst B: u32 = A;");
+
+    let source = preprocessor
+        .process("#include \"a.wgsl\"\n#include \"b.wgsl\"\nfn f() {}", &[])
+        .unwrap();
+
+    assert_eq!(
+        lines(&source),
+        ["const A: u32 = 1u;", "const B: u32 = A;", "fn f() {}"]
+    );
+}
+
+#[test]
+fn defines_select_the_blocks() {
+    let preprocessor = ShaderPreprocessor::new();
+    let source = "\
+#ifdef TEXTURED
+textured
+#ifndef SHADOWS
+no shadows
+#endif
+#else
+colored
+#endif
+#define SHADOWS
+#ifdef SHADOWS
+shadows
+#endif";
+
+    assert_eq!(
+        lines(&preprocessor.process(source, &["TEXTURED"]).unwrap()),
+        ["textured", "no shadows", "shadows"]
+    );
+    assert_eq!(
+        lines(&preprocessor.process(source, &[]).unwrap()),
+        ["colored", "shadows"]
+    );
+}
+
+#[test]
+fn directive_errors() {
+    let preprocessor = ShaderPreprocessor::new();
+
+    for source in [
+        "#ifdef A",
+        "#endif",
+        "#ifdef A\n#else\n#else\n#endif",
+        "#include \"missing.wgsl\"",
+        "#include missing.wgsl",
+        "#pragma once",
+    ] {
+        assert!(preprocessor.process(source, &[]).is_err(), "{source}");
+    }
+}
+
+#[test]
+fn builtin_includes() {
+    let source = ShaderPreprocessor::new()
+        .process("#include \"camera.wgsl\"", &[])
+        .unwrap();
+
+    assert!(source.contains("struct CameraUniform"));
+}
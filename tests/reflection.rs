@@ -0,0 +1,151 @@
+// Bind group layouts and vertex inputs read from the shaders
+use learn_wgpu::{
+    reflection::merge_layout_entries, InstanceRaw, ShaderReflection, Vertex, VertexLayout,
+};
+
+const SHADER: &str = "
+struct Camera {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+@group(1) @binding(2)
+var t_shadow: texture_depth_2d_array;
+@group(1) @binding(3)
+var s_shadow: sampler_comparison;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) tex_coords: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.tex_coords = tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let lit = textureSampleCompareLevel(t_shadow, s_shadow, in.tex_coords, 0, 0.5);
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * lit;
+}
+";
+
+fn mesh_shader() -> ShaderReflection {
+    let source = learn_wgpu::ShaderPreprocessor::new()
+        .process(
+            include_str!("../src/shaders/shader_triangle_interpol_buffer.wgsl"),
+            &[],
+        )
+        .unwrap();
+    ShaderReflection::new(&source).unwrap()
+}
+
+#[test]
+fn layouts_follow_the_bindings() {
+    let reflection = ShaderReflection::new(SHADER).unwrap();
+
+    let camera = reflection.bind_group_layout_entries(0).unwrap();
+    assert_eq!(camera.len(), 1);
+    assert_eq!(camera[0].visibility, wgpu::ShaderStages::VERTEX);
+    assert!(matches!(
+        camera[0].ty,
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            ..
+        }
+    ));
+
+    let textures = reflection.bind_group_layout_entries(1).unwrap();
+    let types = textures.iter().map(|e| e.ty).collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+        ]
+    );
+    assert!(textures
+        .iter()
+        .all(|e| e.visibility == wgpu::ShaderStages::FRAGMENT));
+}
+
+#[test]
+fn merged_layouts_are_visible_to_both_stages() {
+    let reflection = ShaderReflection::new(SHADER).unwrap();
+    let camera = reflection.bind_group_layout_entries(0).unwrap();
+    let mut fragment_camera = camera.clone();
+    fragment_camera[0].visibility = wgpu::ShaderStages::FRAGMENT;
+
+    let merged = merge_layout_entries(&camera, &fragment_camera).unwrap();
+
+    assert_eq!(merged[0].visibility, wgpu::ShaderStages::VERTEX_FRAGMENT);
+    // a texture where the other shader has a buffer
+    let textures = reflection.bind_group_layout_entries(1).unwrap();
+    assert!(merge_layout_entries(&camera, &textures).is_err());
+}
+
+#[test]
+fn mesh_shader_matches_the_vertex_buffers() {
+    mesh_shader()
+        .check_vertex_layouts("vs_main", &[Vertex::desc(), InstanceRaw::desc()])
+        .unwrap();
+}
+
+#[test]
+fn vertex_input_mismatches() {
+    let reflection = mesh_shader();
+
+    // without the instances
+    let missing = reflection
+        .check_vertex_layouts("vs_main", &[Vertex::desc()])
+        .unwrap_err();
+    assert!(missing.to_string().contains("location 5"), "{missing}");
+
+    // floats read as integers
+    let mut attributes = Vertex::desc().attributes.to_vec();
+    attributes[1].format = wgpu::VertexFormat::Uint32x2;
+    let layout = wgpu::VertexBufferLayout {
+        attributes: &attributes,
+        ..Vertex::desc()
+    };
+    let wrong_kind = reflection
+        .check_vertex_layouts("vs_main", &[layout, InstanceRaw::desc()])
+        .unwrap_err();
+    assert!(
+        wrong_kind.to_string().contains("location 1"),
+        "{wrong_kind}"
+    );
+
+    // the same location twice
+    let overlapping = reflection
+        .check_vertex_layouts("vs_main", &[Vertex::desc(), Vertex::desc()])
+        .unwrap_err();
+    assert!(overlapping.to_string().contains("several"), "{overlapping}");
+}
+
+#[test]
+fn naga_errors_are_reported() {
+    let error = ShaderReflection::new("fn f() -> f32 { return undefined; }")
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("undefined"), "{error}");
+}
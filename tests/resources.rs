@@ -0,0 +1,159 @@
+// Loading models and scenes from files
+use cgmath::prelude::*;
+use learn_wgpu::{resources, Camera, Instance, Material, Mesh, Model, MyTexture, Renderer, Scene};
+
+// The test scene of src/models with the replacements,
+// written next to the other temporary files as name
+fn load_patched_gltf(renderer: &Renderer, name: &str, replacements: &[(&str, &str)]) -> Scene {
+    let mut source = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/models/scene.gltf"
+    ))
+    .unwrap()
+    .replace(
+        "../textures/happy-tree.png",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/happy-tree.png"),
+    );
+    for (from, to) in replacements {
+        assert!(source.contains(from), "{from} is not in the scene");
+        source = source.replace(from, to);
+    }
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).unwrap();
+    resources::load_gltf(renderer, &path).unwrap()
+}
+
+#[test]
+fn gltf_orthographic_camera_and_flat_node() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    // the test scene with an orthographic camera and a ground flattened to nothing
+    let scene = load_patched_gltf(
+        &renderer,
+        "learn-wgpu-orthographic.gltf",
+        &[
+            (
+                r#""type": "perspective",
+      "perspective": {
+        "yfov": 0.8,
+        "znear": 0.1,
+        "zfar": 50
+      }"#,
+                r#""type": "orthographic",
+      "orthographic": {
+        "xmag": 4.0,
+        "ymag": 3.0,
+        "znear": 0.1,
+        "zfar": 50
+      }"#,
+            ),
+            (
+                r#""name": "ground",
+      "mesh": 1"#,
+                r#""name": "ground",
+      "mesh": 1,
+      "scale": [1.0, 0.0, 1.0]"#,
+            ),
+        ],
+    );
+
+    // the default camera is used instead
+    assert_eq!(scene.camera().eye, (0.0, 1.0, 5.0).into());
+    for object in scene.objects() {
+        for instance in object.instances() {
+            let rotation: [f32; 4] = instance.rotation.into();
+            assert!(rotation.iter().all(|c| c.is_finite()), "{rotation:?}");
+        }
+    }
+}
+
+#[test]
+fn gltf_mirrored_node() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let scene = load_patched_gltf(
+        &renderer,
+        "learn-wgpu-mirrored.gltf",
+        &[(
+            r#""name": "left cube",
+      "mesh": 0,"#,
+            r#""name": "left cube",
+      "mesh": 0,
+      "scale": [-1, 1, 1],"#,
+        )],
+    );
+
+    // the transform of the left cube in the file: under the "cubes" node
+    let parent = cgmath::Matrix4::from_angle_y(cgmath::Deg(30.0));
+    let expected = parent
+        * cgmath::Matrix4::from_translation((-1.2, 0.5, 0.0).into())
+        * cgmath::Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0);
+    let matches = |instance: &Instance| {
+        let model = instance.model_matrix();
+        (0..4).all(|col| (model[col] - expected[col]).magnitude() < 1e-5)
+    };
+    assert!(scene
+        .objects()
+        .iter()
+        .any(|object| object.instances().iter().any(matches)));
+}
+
+#[test]
+fn gltf_materials_share_their_images() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    // the red material also showing the happy tree
+    let scene = load_patched_gltf(
+        &renderer,
+        "learn-wgpu-shared-image.gltf",
+        &[(
+            r#""name": "red",
+      "pbrMetallicRoughness": {"#,
+            r#""name": "red",
+      "pbrMetallicRoughness": {
+        "baseColorTexture": {
+          "index": 0
+        },"#,
+        )],
+    );
+
+    let materials = &scene.objects()[0].model.materials;
+    assert!(materials[0]
+        .base_color_texture
+        .ptr_eq(&materials[1].base_color_texture));
+}
+
+#[test]
+fn empty_meshes_are_skipped() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    // an object without faces before a triangle
+    let path = std::env::temp_dir().join("learn-wgpu-empty.obj");
+    std::fs::write(
+        &path,
+        "o empty\nv 0 0 0\no triangle\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 2 3 4\n",
+    )
+    .unwrap();
+    let model = resources::load_obj(&renderer, &path).unwrap();
+    assert_eq!(model.meshes.len(), 1);
+    assert_eq!(model.meshes[0].name, "triangle");
+
+    // made by hand, it is drawn as nothing
+    let mut scene = Scene::new(&renderer, Camera::new(renderer.config()));
+    let model = Model {
+        meshes: vec![Mesh::new(renderer.device(), "empty", &[], &[], 0)],
+        materials: vec![Material::new(
+            renderer.device(),
+            renderer.material_bind_group_layout(),
+            "white",
+            MyTexture::from_image(
+                renderer.device(),
+                renderer.queue(),
+                &image::DynamicImage::new_rgba8(1, 1),
+            ),
+        )],
+    };
+    scene.add_model(
+        renderer.device(),
+        model,
+        vec![Instance::new(cgmath::Vector3::zero())],
+    );
+    scene.update(renderer.queue());
+    renderer.capture(&scene);
+}
@@ -0,0 +1,35 @@
+// Shadow settings and the projections of the shadow casting lights
+use cgmath::prelude::*;
+use learn_wgpu::{shadow, Light, Renderer, ShadowSettings};
+
+#[test]
+fn shadow_resolution_is_clamped() {
+    let mut renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let max = renderer.device().limits().max_texture_dimension_2d;
+
+    for (resolution, expected) in [(0, 1), (u32::MAX, max)] {
+        renderer.set_shadow_settings(ShadowSettings {
+            resolution,
+            ..Default::default()
+        });
+        assert_eq!(renderer.shadow_settings().resolution, expected);
+    }
+}
+
+#[test]
+fn wide_spots_have_a_projection() {
+    let light = Light::spot(
+        (0.0, 3.0, 0.0).into(),
+        -cgmath::Vector3::unit_y(),
+        cgmath::Deg(80.0),
+        cgmath::Deg(100.0),
+        [1.0; 3],
+    );
+    let view_proj = shadow::light_view_proj(&light, &ShadowSettings::default()).unwrap();
+
+    let below = view_proj * cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert!((0..4).all(|i| view_proj[i].magnitude().is_finite()));
+    // the point under the spot is in front of it, at the center
+    assert!(below.w > 0.0);
+    assert!(below.x.abs() < 1e-4 && below.y.abs() < 1e-4);
+}
@@ -0,0 +1,15 @@
+// The State gluing the renderer, the scene and the input together
+use winit::event::WindowEvent;
+
+#[test]
+fn events_keep_the_renderer_options() {
+    let mut state = pollster::block_on(learn_wgpu::State::new_headless(16, 16));
+    state.renderer_mut().set_use_color(true);
+    state.renderer_mut().set_show_depth(true);
+
+    // not a toggle key
+    state.input(&WindowEvent::Focused(true));
+
+    assert!(state.renderer().use_color());
+    assert!(state.renderer().show_depth());
+}
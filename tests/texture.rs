@@ -0,0 +1,126 @@
+// Texture creation from image files
+use learn_wgpu::{mytexture, MyTexture, Renderer, TextureAtlas, TextureOptions};
+
+#[test]
+fn invalid_image_is_an_error() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+
+    let result = MyTexture::new(renderer.device(), renderer.queue(), b"not an image");
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn full_mip_chain() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+
+    let texture = MyTexture::new(
+        renderer.device(),
+        renderer.queue(),
+        include_bytes!("../src/textures/happy-tree.png"),
+    )
+    .unwrap();
+
+    // 256x256 down to 1x1
+    assert_eq!(texture.texture.mip_level_count(), 9);
+}
+
+#[test]
+fn srgb_mips_keep_the_brightness() {
+    // half black and half white: the last level is half as bright,
+    // which is 188 in sRGB, not 128
+    let checker = image::RgbaImage::from_fn(2, 2, |x, y| {
+        let c = if (x + y) % 2 == 0 { 255 } else { 0 };
+        image::Rgba([c, c, c, 255])
+    });
+
+    let srgb = mytexture::rgba8_mip_chain(checker.clone(), true);
+    let linear = mytexture::rgba8_mip_chain(checker.clone(), false);
+
+    assert_eq!(srgb[0], checker);
+    assert_eq!(srgb[1].get_pixel(0, 0).0, [188, 188, 188, 255]);
+    assert!(linear[1].get_pixel(0, 0).0[0].abs_diff(128) <= 1);
+}
+
+#[test]
+fn cache_shares_textures() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let textures = renderer.textures();
+
+    let first = textures
+        .load(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/happy-tree.png"),
+            &TextureOptions::default(),
+        )
+        .unwrap();
+    // the same file through another path
+    let second = textures
+        .load(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/models/../textures/happy-tree.png"
+            ),
+            &TextureOptions::default(),
+        )
+        .unwrap();
+
+    assert!(first.ptr_eq(&second));
+    assert_eq!(textures.len(), 1);
+}
+
+#[test]
+fn cache_missing_file_is_an_error() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+
+    let result = renderer
+        .textures()
+        .load("does/not/exist.png", &TextureOptions::default());
+
+    assert!(result.is_err());
+    assert!(renderer.textures().is_empty());
+}
+
+#[test]
+fn cache_replace_updates_handles() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/textures/happy-tree.png");
+    let handle = renderer
+        .textures()
+        .load(path, &TextureOptions::default())
+        .unwrap();
+
+    let image = image::RgbaImage::new(4, 2).into();
+    let texture = MyTexture::from_image(renderer.device(), renderer.queue(), &image);
+    renderer.textures().replace(path, texture);
+
+    assert_eq!(handle.texture().texture.width(), 4);
+
+    // and back to the file
+    renderer.textures().reload(path).unwrap();
+    assert_eq!(handle.texture().texture.width(), 256);
+}
+
+#[test]
+fn atlas_checks_its_images() {
+    let renderer = pollster::block_on(Renderer::new_headless(16, 16));
+    let image = |size| image::RgbaImage::new(size, size).into();
+    let options = TextureOptions::default();
+
+    let empty = TextureAtlas::new(
+        renderer.device(),
+        renderer.queue(),
+        &[image(8), image(0)],
+        &options,
+    );
+    assert!(empty.is_err());
+
+    // 48x24 would have 6 levels, the padding of 4 texels only protects 3
+    let atlas = TextureAtlas::new(
+        renderer.device(),
+        renderer.queue(),
+        &[image(16), image(16)],
+        &options,
+    )
+    .unwrap();
+    assert_eq!(atlas.texture().texture.mip_level_count(), 3);
+}
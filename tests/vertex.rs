@@ -0,0 +1,116 @@
+// Vertex data computed on the CPU
+use learn_wgpu::{
+    vertex::{compute_tangents, fill_missing_tangents},
+    InstanceRaw, Vertex, VertexLayout,
+};
+
+fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+    }
+}
+
+#[test]
+fn tangents_follow_the_u_axis() {
+    // a square facing +z, u going right and v going down
+    let mut vertices = [
+        Vertex::new([-0.5, -0.5, 0.0], [0.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.0], [1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+
+    compute_tangents(&mut vertices, &[0, 1, 2, 0, 2, 3]);
+
+    for vertex in vertices {
+        assert_close(vertex.tangent(), [1.0, 0.0, 0.0, 1.0]);
+    }
+}
+
+#[test]
+fn given_tangents_are_kept() {
+    // the same square, with a tangent given for one vertex only
+    let given = [0.0, 1.0, 0.0, -1.0];
+    let mut vertices = [
+        Vertex::new([-0.5, -0.5, 0.0], [0.0, 1.0], [0.0, 0.0, 1.0]).with_tangent(given),
+        Vertex::new([0.5, -0.5, 0.0], [1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+
+    fill_missing_tangents(&mut vertices, &[0, 1, 2, 0, 2, 3]);
+
+    assert_eq!(vertices[0].tangent(), given);
+    for vertex in &vertices[1..] {
+        assert_close(vertex.tangent(), [1.0, 0.0, 0.0, 1.0]);
+    }
+}
+
+#[test]
+fn mirrored_texture_flips_the_bitangent() {
+    // the same square with the texture mirrored horizontally
+    let mut vertices = [
+        Vertex::new([-0.5, -0.5, 0.0], [1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.0], [0.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+
+    compute_tangents(&mut vertices, &[0, 1, 2, 0, 2, 3]);
+
+    for vertex in vertices {
+        assert_close(vertex.tangent(), [-1.0, 0.0, 0.0, -1.0]);
+    }
+}
+
+// (location, offset, format) of each attribute
+fn attributes(layout: &wgpu::VertexBufferLayout) -> Vec<(u32, u64, wgpu::VertexFormat)> {
+    layout
+        .attributes
+        .iter()
+        .map(|a| (a.shader_location, a.offset, a.format))
+        .collect()
+}
+
+#[test]
+fn derived_vertex_layout() {
+    use wgpu::VertexFormat::*;
+    let layout = Vertex::desc();
+
+    assert_eq!(layout.array_stride, 48);
+    assert_eq!(layout.step_mode, wgpu::VertexStepMode::Vertex);
+    assert_eq!(
+        attributes(&layout),
+        [
+            (0, 0, Float32x3),
+            (1, 12, Float32x2),
+            (2, 20, Float32x3),
+            (3, 32, Float32x4)
+        ]
+    );
+}
+
+#[test]
+fn derived_instance_layout() {
+    use wgpu::VertexFormat::*;
+    let layout = InstanceRaw::desc();
+
+    assert_eq!(layout.array_stride, 148);
+    assert_eq!(layout.step_mode, wgpu::VertexStepMode::Instance);
+    // one location per column of the matrix
+    assert_eq!(
+        attributes(&layout),
+        [
+            (5, 0, Float32x4),
+            (6, 16, Float32x4),
+            (7, 32, Float32x4),
+            (8, 48, Float32x4),
+            (9, 64, Float32x4),
+            (10, 80, Float32x3),
+            (11, 92, Float32x3),
+            (12, 104, Float32x3),
+            (13, 116, Float32x4),
+            (14, 132, Float32x4)
+        ]
+    );
+}